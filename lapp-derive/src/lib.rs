@@ -0,0 +1,301 @@
+//! `#[derive(Args)]` builds a lapp spec from the fields of a struct.
+//!
+//! Use it through `lapp` with the `derive` feature:
+//!
+//! ```rust,ignore
+//! #[derive(lapp::Args)]
+//! struct Config {
+//!     /// verbose output
+//!     #[lapp(short = 'v')]
+//!     verbose: bool,
+//!     /// number of lines
+//!     #[lapp(short = 'n', default = "10")]
+//!     lines: i32,
+//!     /// files to process
+//!     #[lapp(positional)]
+//!     files: Vec<String>,
+//! }
+//!
+//! let config = Config::parse();
+//! ```
+//!
+//! The doc comment of each field becomes its help. `Vec<T>` fields are
+//! multiple flags, and `Option<T>` fields are optional.
+//!
+//! Not every type can be collected in a `Vec`; for instance this fails to compile:
+//!
+//! ```compile_fail
+//! extern crate lapp;
+//! #[derive(lapp::Args)]
+//! struct Flags {
+//!     switches: Vec<bool>,
+//! }
+//! # fn main() {}
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{Data, DeriveInput, Fields, Field, Type, PathSegment, PathArguments, GenericArgument,
+    TypeParamBound, Expr, Lit, LitChar, LitStr, Meta};
+
+type Result<T> = ::std::result::Result<T,syn::Error>;
+
+#[proc_macro_derive(Args, attributes(lapp))]
+pub fn derive_args(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into()
+    }
+}
+
+// how a field appears in the spec, and how its value is fetched
+struct FlagDef {
+    ident: syn::Ident,
+    name: String,
+    short: Option<char>,
+    positional: bool,
+    default: Option<String>,
+    help: String,
+    lapp_type: &'static str,
+    getter: String,
+    multiple: bool,
+    optional: bool,
+}
+
+impl FlagDef {
+    // builds the line in the spec at run-time, like any other ArgSpec
+    fn arg_spec(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let mut spec = if self.positional {
+            quote! { ::lapp::ArgSpec::positional(#name) }
+        } else {
+            quote! { ::lapp::ArgSpec::long(#name) }
+        };
+        if let Some(ch) = self.short {
+            spec = quote! { #spec.short(#ch) };
+        }
+        if self.multiple {
+            spec = quote! { #spec.multiple() };
+        }
+        // a simple flag needs no type
+        if self.lapp_type != "bool" || self.default.is_some() {
+            let lapp_type = self.lapp_type;
+            spec = quote! { #spec.type_name(#lapp_type) };
+            if self.optional {
+                spec = quote! { #spec.optional() };
+            }
+            if let Some(ref d) = self.default {
+                let quoted = self.lapp_type == "string" && ! (d.starts_with('\'') || d.starts_with('"'));
+                spec = if quoted {
+                    quote! { #spec.default_str(#d) }
+                } else {
+                    quote! { #spec.default(#d) }
+                };
+            }
+        }
+        if ! self.help.is_empty() {
+            let help = &self.help;
+            spec = quote! { #spec.help(#help) };
+        }
+        spec
+    }
+}
+
+fn last_segment(ty: &Type) -> Option<&PathSegment> {
+    match *ty {
+        Type::Path(ref p) => p.path.segments.last(),
+        _ => None
+    }
+}
+
+// the first type parameter, as in Vec<T>
+fn type_param(seg: &PathSegment) -> Option<&Type> {
+    if let PathArguments::AngleBracketed(ref args) = seg.arguments {
+        for arg in &args.args {
+            if let GenericArgument::Type(ref t) = *arg {
+                return Some(t);
+            }
+        }
+    }
+    None
+}
+
+// the name of a boxed trait, as in Box<dyn Read>
+fn trait_name(ty: &Type) -> Option<String> {
+    match *ty {
+        Type::TraitObject(ref t) => t.bounds.iter().filter_map(|b| match *b {
+            TypeParamBound::Trait(ref tb) => tb.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None
+        }).next(),
+        _ => last_segment(ty).map(|s| s.ident.to_string())
+    }
+}
+
+// the lapp type and getter suffix of a scalar Rust type
+fn scalar(ty: &Type) -> Option<(&'static str, &'static str)> {
+    let seg = last_segment(ty)?;
+    Some(match seg.ident.to_string().as_str() {
+        "bool" => ("bool","bool"),
+        "i32" => ("integer","integer"),
+        "u32" => ("u32","u32"),
+        "u64" => ("u64","u64"),
+        "usize" => ("usize","usize"),
+        "f32" => ("float","float"),
+        "i64" => ("i64","i64"),
+        "f64" => ("f64","f64"),
+        "String" => ("string","string"),
+        "PathBuf" => ("path","path"),
+        "Url" => ("url","url"),
+        "Regex" => ("regex","regex"),
+        "Box" => match trait_name(type_param(seg)?)?.as_str() {
+            "Read" => ("infile","infile"),
+            "Write" => ("outfile","outfile"),
+            _ => return None
+        },
+        _ => return None
+    })
+}
+
+fn has_array_getter(getter: &str) -> bool {
+    matches!(getter, "string" | "integer" | "float" | "f64" | "u32" | "u64" | "usize" | "i64" | "path")
+}
+
+fn flag_def(field: &Field) -> Result<FlagDef> {
+    let ident = match field.ident {
+        Some(ref ident) => ident.clone(),
+        None => return Err(syn::Error::new(Span::call_site(),"lapp: fields must be named"))
+    };
+    let unsupported = || syn::Error::new_spanned(&field.ty,"lapp: unsupported field type");
+    let mut def = FlagDef {
+        name: ident.to_string().replace('_',"-"),
+        ident,
+        short: None,
+        positional: false,
+        default: None,
+        help: String::new(),
+        lapp_type: "",
+        getter: String::new(),
+        multiple: false,
+        optional: false,
+    };
+    let seg = last_segment(&field.ty).ok_or_else(unsupported)?;
+    match seg.ident.to_string().as_str() {
+        "Vec" => {
+            let elem = type_param(seg).ok_or_else(unsupported)?;
+            let t = scalar(elem).ok_or_else(unsupported)?;
+            // only these types have a getter for multiple values
+            if ! has_array_getter(t.1) {
+                return Err(syn::Error::new_spanned(elem,"lapp: unsupported element type for Vec"));
+            }
+            def.lapp_type = t.0;
+            def.getter = format!("get_{}s",t.1);
+            def.multiple = true;
+        },
+        "Option" => {
+            let t = scalar(type_param(seg).ok_or_else(unsupported)?).ok_or_else(unsupported)?;
+            def.lapp_type = t.0;
+            def.getter = format!("get_{}_result",t.1);
+            def.optional = true;
+        },
+        "HashMap" => {
+            def.lapp_type = "keyval";
+            def.getter = "get_string_map".into();
+        },
+        _ => {
+            let t = scalar(&field.ty).ok_or_else(unsupported)?;
+            def.lapp_type = t.0;
+            def.getter = format!("get_{}",t.1);
+        }
+    }
+
+    let mut help = Vec::new();
+    for attr in &field.attrs {
+        if attr.path().is_ident("doc") {
+            if let Meta::NameValue(ref nv) = attr.meta {
+                if let Expr::Lit(ref e) = nv.value {
+                    if let Lit::Str(ref s) = e.lit {
+                        help.push(s.value().trim().to_string());
+                    }
+                }
+            }
+        } else if attr.path().is_ident("lapp") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("short") {
+                    let ch: LitChar = meta.value()?.parse()?;
+                    def.short = Some(ch.value());
+                } else if meta.path.is_ident("default") {
+                    let s: LitStr = meta.value()?.parse()?;
+                    def.default = Some(s.value());
+                } else if meta.path.is_ident("positional") {
+                    def.positional = true;
+                } else {
+                    return Err(meta.error("lapp: expecting 'short', 'default' or 'positional'"));
+                }
+                Ok(())
+            })?;
+        }
+    }
+    def.help = help.join(" ");
+    Ok(def)
+}
+
+fn expand(input: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref s) => match s.fields {
+            Fields::Named(ref f) => &f.named,
+            _ => return Err(syn::Error::new_spanned(input,"lapp: only structs with named fields"))
+        },
+        _ => return Err(syn::Error::new_spanned(input,"lapp: only structs with named fields"))
+    };
+    let mut defs = Vec::new();
+    for field in fields {
+        defs.push(flag_def(field)?);
+    }
+
+    let specs = defs.iter().map(|def| def.arg_spec());
+    let values = defs.iter().map(|def| {
+        let ident = &def.ident;
+        let getter = syn::Ident::new(&def.getter,Span::call_site());
+        let name = &def.name;
+        if def.optional {
+            quote! { #ident: args.#getter(#name).ok() }
+        } else {
+            quote! { #ident: args.#getter(#name) }
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// the lapp spec generated from the fields
+            pub fn spec() -> &'static str {
+                static SPEC: ::std::sync::OnceLock<String> = ::std::sync::OnceLock::new();
+                SPEC.get_or_init(|| {
+                    let specs = [ #( #specs ),* ];
+                    specs.iter().map(|spec| spec.spec_line() + "\n").collect()
+                })
+            }
+
+            /// fill in the fields from already-parsed flags
+            pub fn from_args(args: &::lapp::Args) -> Self {
+                #name {
+                    #( #values, )*
+                }
+            }
+
+            /// parse the command-line, quitting on error
+            pub fn parse() -> Self {
+                let args = ::lapp::parse_args(Self::spec());
+                Self::from_args(&args)
+            }
+        }
+    })
+}
@@ -0,0 +1,87 @@
+extern crate lapp;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(lapp::Args)]
+struct Config {
+    /// verbose output
+    #[lapp(short = 'v')]
+    verbose: bool,
+    /// number of lines
+    #[lapp(short = 'n', default = "10")]
+    lines: i32,
+    /// output file
+    #[lapp(default = "stdout")]
+    output_file: String,
+    scale: Option<f32>,
+    include: Vec<String>,
+    config: Option<PathBuf>,
+    define: HashMap<String,String>,
+    /// files to process
+    #[lapp(positional)]
+    files: Vec<String>,
+}
+
+fn args(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn spec_from_fields() {
+    assert_eq!(Config::spec(), "  -v, --verbose verbose output
+  -n, --lines (integer default 10) number of lines
+  --output-file (string default 'stdout') output file
+  --scale (float optional)
+  --include... (string)
+  --config (path optional)
+  --define (keyval)
+  <files> (string...) files to process
+");
+}
+
+#[test]
+fn values_from_args() {
+    let mut aa = lapp::Args::new(Config::spec());
+    aa.parse_spec().unwrap();
+    aa.try_parse_from(args(&["-v","--include","a","--define","x=1","--define","y=2","one","two"])).unwrap();
+    let config = Config::from_args(&aa);
+    assert!(config.verbose);
+    assert_eq!(config.lines, 10);
+    assert_eq!(config.output_file, "stdout");
+    assert_eq!(config.scale, None);
+    assert_eq!(config.include, ["a"]);
+    assert_eq!(config.config, None);
+    assert_eq!(config.define.len(), 2);
+    assert_eq!(config.files, ["one","two"]);
+}
+
+#[derive(lapp::Args)]
+struct Lists {
+    strings: Vec<String>,
+    ints: Vec<i32>,
+    floats: Vec<f32>,
+    doubles: Vec<f64>,
+    small: Vec<u32>,
+    big: Vec<u64>,
+    sizes: Vec<usize>,
+    offsets: Vec<i64>,
+    paths: Vec<PathBuf>,
+}
+
+#[test]
+fn every_vec_type() {
+    let mut aa = lapp::Args::new(Lists::spec());
+    aa.try_parse_from(args(&["--strings","a","--ints","-1","--floats","0.5","--doubles","0.1",
+        "--small","1","--big","8000000000","--sizes","2","--offsets","-3","--paths","x.txt"])).unwrap();
+    let lists = Lists::from_args(&aa);
+    assert_eq!(lists.strings, ["a"]);
+    assert_eq!(lists.ints, [-1]);
+    assert_eq!(lists.floats, [0.5]);
+    assert_eq!(lists.doubles, [0.1]);
+    assert_eq!(lists.small, [1]);
+    assert_eq!(lists.big, [8000000000]);
+    assert_eq!(lists.sizes, [2]);
+    assert_eq!(lists.offsets, [-3]);
+    assert_eq!(lists.paths, [PathBuf::from("x.txt")]);
+}
@@ -0,0 +1,123 @@
+// Building flag definitions in code, rather than in a spec string
+
+/// a single flag definition, built up with chained calls and turned into a spec line.
+///
+/// ```
+/// use lapp::{Args, ArgSpec};
+/// let mut args = Args::from_specs(&[
+///     ArgSpec::long("verbose").short('v').boolean().help("enable verbose output"),
+///     ArgSpec::long("output").short('o').default_str("stdout").help("output file"),
+///     ArgSpec::positional("file").type_name("string"),
+/// ]);
+/// args.parse_command_line(vec!["-v".into(), "in.txt".into()]).unwrap();
+/// assert!(args.get_bool("verbose"));
+/// assert_eq!(args.get_string("output"), "stdout");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ArgSpec {
+    name: String,
+    short: Option<char>,
+    positional: bool,
+    multiple: bool,
+    optional: bool,
+    vtype: Option<String>,
+    default: Option<String>,
+    help: String,
+}
+
+impl ArgSpec {
+    /// a flag with a long name
+    pub fn long(name: &str) -> ArgSpec {
+        ArgSpec { name: name.into(), ..Default::default() }
+    }
+
+    /// a positional argument
+    pub fn positional(name: &str) -> ArgSpec {
+        ArgSpec { name: name.into(), positional: true, ..Default::default() }
+    }
+
+    /// also has a short name
+    pub fn short(mut self, ch: char) -> Self {
+        self.short = Some(ch);
+        self
+    }
+
+    /// a simple flag, which is false unless given
+    pub fn boolean(mut self) -> Self {
+        self.vtype = None;
+        self.default = None;
+        self
+    }
+
+    /// the type, like "integer", "infile" or a range like "1..10"
+    pub fn type_name(mut self, name: &str) -> Self {
+        self.vtype = Some(name.into());
+        self
+    }
+
+    /// the flag may be given more than once
+    pub fn multiple(mut self) -> Self {
+        self.multiple = true;
+        self
+    }
+
+    /// the flag need not be given, even without a default
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// default value, as it would appear in a spec, e.g. "10" or "stdin"
+    pub fn default(mut self, value: &str) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// default string value
+    pub fn default_str(mut self, value: &str) -> Self {
+        self.default = Some(format!("'{}'",value));
+        self
+    }
+
+    /// the help text which follows the flag in the spec
+    pub fn help(mut self, text: &str) -> Self {
+        self.help = text.into();
+        self
+    }
+
+    /// the equivalent line in a spec
+    pub fn spec_line(&self) -> String {
+        let mut res = String::from("  ");
+        if self.positional {
+            res += &format!("<{}>",self.name);
+        } else {
+            if let Some(ch) = self.short {
+                res += &format!("-{}, ",ch);
+            }
+            res += &format!("--{}",self.name);
+            if self.multiple {
+                res += "...";
+            }
+        }
+        let mut modifiers = Vec::new();
+        if let Some(ref t) = self.vtype {
+            // positional arguments are collected when the type is multiple
+            let dots = if self.positional && self.multiple {"..."} else {""};
+            modifiers.push(format!("{}{}",t,dots));
+        }
+        if self.optional {
+            modifiers.push("optional".into());
+        }
+        if let Some(ref d) = self.default {
+            modifiers.push(format!("default {}",d));
+        }
+        if ! modifiers.is_empty() {
+            res += &format!(" ({})",modifiers.join(" "));
+        }
+        if ! self.help.is_empty() {
+            res += " ";
+            res += &self.help;
+        }
+        res
+    }
+}
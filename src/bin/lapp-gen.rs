@@ -3,6 +3,7 @@ use std::env;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
 
 const USAGE: &'static str = "
 lapp-gen, generate Rust code from lapp specification files
@@ -22,11 +23,31 @@ generation mode, which defaults to 'validate'.
 If lapp_gen is in 'validate' mode, any command-line arguments passed are parsed and the
 results displayed. This allows you to prototype a command-line interface rapidly.
 
+The spec file may pull in other spec files with '@include \"file\"' lines,
+which are read relative to the spec file.
+
+If the first argument is --toml-spec, the spec file is read as a TOML table of
+[flags.NAME] and [args.NAME] entries (needs the 'toml-spec' feature).
+
 If the extra field is 'vars', it prints out a set of declarations that access the flags.
 If 'struct', it prints out a suitable struct declaration for accessing the flags, which
 is meant to be brought into your program using 'include!'.
 ";
 
+#[cfg(feature = "toml-spec")]
+fn from_toml(txt: &str) -> lapp::Args<'static> {
+    lapp::Args::from_toml(txt).unwrap_or_else(|e| {
+        eprintln!("lapp-gen error: {}", e);
+        ::std::process::exit(1);
+    })
+}
+
+#[cfg(not(feature = "toml-spec"))]
+fn from_toml(_txt: &str) -> lapp::Args<'static> {
+    eprintln!("lapp-gen error: built without the 'toml-spec' feature");
+    ::std::process::exit(1);
+}
+
 enum Mode {
     Validate,
     Vars,
@@ -61,7 +82,26 @@ fn main() {
     f.read_to_string(&mut txt)
         .expect(&format!("Unable to read UTF-8 from {}. Error", lapp_file));
 
-    let mut args = lapp::Args::new(&txt);
+    let toml_spec = env::args().nth(1).is_some_and(|a| a == "--toml-spec");
+    let mut args = if toml_spec {
+        // the flag is ours, not for the spec being tested
+        from_toml(&txt).start(2)
+    } else {
+        lapp::Args::new(&txt)
+    };
+    if ! toml_spec {
+        let dir = Path::new(lapp_file).parent().unwrap_or(Path::new(""));
+        // report all the errors at once
+        let errors = args.validate_spec_with_context(dir);
+        if ! errors.is_empty() {
+            for e in &errors {
+                eprintln!("lapp-gen error: {}", e);
+            }
+            ::std::process::exit(1);
+        }
+    }
+
+    args.set_help_text("(lapp-gen: LAPP_GEN=\"<spec> vars\" or \"<spec> struct\" generates code)");
 
     match mode {
         Mode::Vars => {
@@ -75,6 +115,7 @@ fn main() {
                 .expect("Could not write to stdout. Error");
         }
         Mode::Validate => {
+            println!("{}", args.short_usage());
             args.dump();
         }
     };
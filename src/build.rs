@@ -0,0 +1,43 @@
+//! Checking spec files at build time.
+//!
+//! In `build.rs`, a bad spec can be made into a compile error:
+//!
+//! ```rust,ignore
+//! extern crate lapp;
+//!
+//! fn main() {
+//!     lapp::build::check_spec_file("src/args.lapp");
+//! }
+//! ```
+
+use std::fs;
+use std::path::Path;
+use super::Args;
+use types::*;
+
+/// parse a spec file, and any files it includes, reporting all the errors.
+pub fn validate_spec_file(path: &Path) -> Result<()> {
+    let txt = match fs::read_to_string(path) {
+        Ok(txt) => txt,
+        Err(e) => return lapp_error(format!("cannot read spec '{}': {}",path.display(),e))
+    };
+    let mut args = Args::new(&txt);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let errors = args.validate_spec_with_context(dir);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        let errors: Vec<String> = errors.iter().map(|e| format!("{}: {}",path.display(),e)).collect();
+        lapp_error(errors.join("\n"))
+    }
+}
+
+/// validate a spec file, panicking on error so the build fails.
+/// Cargo is also told to rebuild when the file changes.
+pub fn check_spec_file<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}",path.display());
+    if let Err(e) = validate_spec_file(path) {
+        panic!("lapp spec error in {}", e);
+    }
+}
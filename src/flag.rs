@@ -1,5 +1,6 @@
 // Flag struct
 
+use std::cell::Cell;
 use super::types::*;
 
 #[derive(Default)]
@@ -9,28 +10,144 @@ pub struct Flag {
     pub vtype: Type,
     pub value: Value,
     pub defval: Value,
+    // explicitly given on the command-line
     pub is_set: bool,
+    // given, or filled in from the default
+    pub has_value: bool,
     pub is_multiple: bool,
+    // need not be given, even without a default
+    pub is_optional: bool,
+    // left out of the usage line, but not the help
+    pub synopsis_hidden: bool,
     pub pos: usize,
     pub help: String,
+    // the '[group: Name]' section this flag was declared in
+    pub group: String,
     pub constraint: Option<Box< Fn(Value) -> Result<Value> >>,
+    // human-readable version of the constraint
+    pub constraint_desc: String,
+    // the bounds of a range like '(1..10)', also checked against the default
+    pub range: Option<(Value,Value)>,
     pub strings: Vec<String>,
     pub defstr: String,
+    // the default from the spec, restored by clear()
+    pub original_defval: Value,
+    pub original_defstr: String,
     pub overriden: bool,
+    pub min_count: Option<usize>,
+    pub max_count: Option<usize>,
+    // least number of values in an inline list like '(integer...)'
+    pub min_length: Option<usize>,
+    // value used when the flag is given without a value
+    pub defval_if_present: Option<Value>,
+    pub defstr_if_present: String,
+    // take the value of this other flag if not given
+    pub default_from: Option<String>,
+    // a positional which ends the parse; the rest belongs to the subcommand
+    pub subcommand: bool,
+    // expand values as file patterns; true if there must be a match
+    #[cfg(feature = "glob")]
+    pub glob: Option<bool>,
+    // read by a get_* method; only tracked in warn-unused mode
+    pub accessed: Cell<bool>,
+}
+
+/// a read-only view of a flag definition
+pub struct FlagInfo<'a> {
+    pub name: &'a str,
+    pub short: Option<char>,
+    pub vtype: &'a Type,
+    pub is_multiple: bool,
+    pub position: Option<usize>,
+    pub help: &'a str,
+    pub group: &'a str,
 }
 
 impl Flag {
+    pub fn info(&self) -> FlagInfo<'_> {
+        FlagInfo {
+            name: &self.long,
+            short: if self.short == '\0' {None} else {Some(self.short)},
+            vtype: &self.vtype,
+            is_multiple: self.is_multiple,
+            position: self.position(),
+            help: &self.help,
+            group: &self.group,
+        }
+    }
+
     pub fn set_value_from_string(&mut self, arg: &str) -> Result<()> {
+        #[cfg(feature = "glob")]
+        {
+            if let Some(required) = self.glob {
+                return self.set_values_from_glob(arg,required);
+            }
+        }
         let mut v = self.vtype.parse_string(arg)?;
         // there may be a constrait on this flag value
         if let Some(ref constraint) = self.constraint {
             v = constraint(v)?;
         }
+        if let (Some(min), Ok(arr)) = (self.min_length, v.as_array()) {
+            if arr.len() < min {
+                return lapp_error(format!("flag '{}': expected at least {} values in inline list, got {}",self.long,min,arr.len()));
+            }
+        }
         self.strings.push(arg.to_string());
         self.set_value(v)?;
         Ok(())
     }
 
+    /// set several values at once, as read from an environment variable.
+    /// The command-line parser sees one value per occurrence, so it uses
+    /// `set_value_from_string` directly.
+    /// The values of an array flag like '(integer...)' are joined and split again.
+    /// Unlike the command-line, where a bad value is kept as a `Value::Error` and only
+    /// reported when the flag is read, this stops with an error at the first bad value,
+    /// giving its index.
+    pub(crate) fn set_value_from_strings(&mut self, args: &[&str]) -> Result<()> {
+        if let Type::Arr(_) = self.vtype {
+            return self.set_value_from_string(&args.join(" "));
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let res = self.set_value_from_string(arg).and_then(|_| {
+                let last = match self.value {
+                    Value::Arr(ref arr) if self.is_multiple => arr.last().map(|b| &**b),
+                    ref v => Some(v)
+                };
+                match last {
+                    Some(Value::Error(e)) => lapp_error(e.clone()),
+                    _ => Ok(())
+                }
+            });
+            if let Err(e) = res {
+                return lapp_error(format!("flag '{}': bad value at index {}: {}",self.long,i,e));
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "glob")]
+    fn set_values_from_glob(&mut self, arg: &str, required: bool) -> Result<()> {
+        let paths = match ::glob::glob(arg) {
+            Ok(paths) => paths,
+            Err(e) => return lapp_error(format!("flag '{}': bad pattern '{}': {}",self.long,arg,e))
+        };
+        let mut count = 0;
+        for path in paths {
+            match path {
+                Ok(p) => self.set_value(Value::Str(p.to_string_lossy().into_owned()))?,
+                Err(e) => return lapp_error(format!("flag '{}': {}",self.long,e))
+            }
+            count += 1;
+        }
+        if count == 0 && required {
+            return lapp_error(format!("flag '{}': no files match '{}'",self.long,arg));
+        }
+        self.strings.push(arg.to_string());
+        Ok(())
+    }
+
     pub fn set_default_from_string(&mut self, arg: &str, infer: bool) -> Result<()> {
         self.defstr = arg.into();
         if infer { // (default <str>)
@@ -39,57 +156,169 @@ impl Flag {
         } else { // (<type> default <str>)
             // type has already been set - coerce value.
             self.defval = Value::from_value(arg,&self.vtype)?;
+            self.check_default(&self.defval,arg)?;
         }
         Ok(())
     }
 
+    // a default must satisfy any constraint; with a range, it must be the range's type
+    fn check_default(&self, v: &Value, arg: &str) -> Result<()> {
+        let values = match *v {
+            Value::Arr(ref arr) => arr.iter().map(|b| (**b).clone()).collect(),
+            ref v => vec![v.clone()]
+        };
+        for v in values {
+            if let Some((ref lo, ref hi)) = self.range {
+                match (in_range(&v,lo,hi), v) {
+                    (Some(true), _) => {},
+                    (Some(false), _) =>
+                        return lapp_error(format!("flag '{}': default {} is outside declared {}",self.long,arg,self.constraint_desc)),
+                    (None, Value::Error(e)) =>
+                        return lapp_error(format!("flag '{}': bad default: {}",self.long,e)),
+                    (None, _) =>
+                        return lapp_error(format!("flag '{}': default {} is not {}",self.long,arg,self.vtype.short_name())),
+                }
+            } else if let Some(ref constraint) = self.constraint {
+                constraint(v)?;
+            }
+        }
+        Ok(())
+    }
+
+    // a default given at run-time must match the flag's type exactly
+    pub fn set_default(&mut self, arg: &str) -> Result<()> {
+        let v = self.vtype.parse_string(arg)?;
+        if let Value::Error(ref e) = v {
+            return lapp_error(format!("flag '{}': bad default: {}",self.long,e));
+        }
+        self.check_default(&v,arg)?;
+        if self.has_value && ! self.is_set {
+            // already parsed, so the old default is in use
+            self.value = v.clone();
+            self.strings = vec![arg.into()];
+        }
+        self.defval = v;
+        self.defstr = arg.into();
+        Ok(())
+    }
+
+    pub fn set_default_if_present(&mut self, arg: &str) -> Result<()> {
+        self.defstr_if_present = arg.into();
+        self.defval_if_present = Some(Value::from_value(arg,&self.vtype)?);
+        Ok(())
+    }
+
+    pub fn set_value_if_present(&mut self) -> Result<()> {
+        let v = self.defval_if_present.clone().unwrap_or_default();
+        self.strings.push(self.defstr_if_present.clone());
+        self.set_value(v)
+    }
+
+    // a bare range like '(1..10)' takes its type from the bounds.
+    // After a type, like '(f64 0..1)', the bounds have the flag's type
     pub fn set_range_constraint(&mut self, b1: &str, b2: &str) -> Result<()> {
-        let b1 = Value::from_value(b1,&Type::None)?;
-        let b2 = Value::from_value(b2,&Type::None)?;
-        if b1.type_of() != b2.type_of() {
-            return error("range values must be same type");
-        }
-        let tn = b1.type_of().short_name();
-        if ! (tn == "integer" || tn == "float") {
-            return error("range values must be integer or float");
-        }
-        self.vtype = b1.type_of();
-
-        if tn == "integer" {
-            let i1 = b1.as_int().unwrap();
-            let i2 = b2.as_int().unwrap();
-            let msg = format!("flag '{}' out of range {}..{}",self.long,i1,i2);
-            self.constraint = Some(Box::new(
-                move |v| {
-                    let i = v.as_int().unwrap();
-                    if i < i1 || i > i2 {
-                        return error(&msg);
+        if self.vtype == Type::None {
+            let t1 = Value::from_value(b1,&Type::None)?.type_of();
+            let t2 = Value::from_value(b2,&Type::None)?.type_of();
+            if t1 != t2 {
+                return lapp_error("range values must be same type");
+            }
+            if ! (t1 == Type::Int || t1 == Type::Float) {
+                return lapp_error("range values must be integer or float");
+            }
+            self.vtype = t1;
+        }
+        let bound = |b: &str| match self.vtype.parse_string(b)? {
+            Value::Error(e) => lapp_error(format!("bad range value: {}",e)),
+            v => Ok(v)
+        };
+        let (lo, hi) = (bound(b1)?, bound(b2)?);
+        let msg = format!("flag '{}' out of range {}..{}",self.long,b1,b2);
+        self.constraint_desc = format!("range {}..{}",b1,b2);
+        self.range = Some((lo.clone(),hi.clone()));
+        self.constraint = Some(Box::new(
+            move |v| {
+                if in_range(&v,&lo,&hi) == Some(false) {
+                    return lapp_error(&msg);
+                }
+                Ok(v)
+            }
+        ));
+        Ok(())
+    }
+
+    // 'positive' excludes zero, 'non-negative' does not
+    pub fn set_sign_constraint(&mut self, positive: bool) {
+        let desc = if positive {"positive"} else {"non-negative"};
+        let long = self.long.clone();
+        self.constraint_desc = desc.into();
+        self.constraint = Some(Box::new(
+            move |v| {
+                let ok = match v {
+                    Value::Int(n) => n > 0 || (n == 0 && ! positive),
+                    Value::Float(x) => x > 0.0 || (x == 0.0 && ! positive),
+                    _ => true
+                };
+                if ! ok {
+                    let s = match v {
+                        Value::Int(n) => n.to_string(),
+                        Value::Float(x) => x.to_string(),
+                        _ => String::new()
+                    };
+                    return lapp_error(format!("flag '{}': {} is not {}",long,s,desc));
+                }
+                Ok(v)
+            }
+        ));
+    }
+
+    #[cfg(feature = "url-type")]
+    pub fn set_https_constraint(&mut self) {
+        let long = self.long.clone();
+        self.constraint_desc = "https only".into();
+        self.constraint = Some(Box::new(
+            move |v| {
+                if let Value::Str(ref s) = v {
+                    if ! s.starts_with("https:") {
+                        return lapp_error(format!("flag '{}': '{}' is not an https URL",long,s));
                     }
-                    Ok(Value::Int(i))
                 }
-            ));
-        } else {
-            let x1 = b1.as_float().unwrap();
-            let x2 = b2.as_float().unwrap();
-            let msg = format!("flag '{}' out of range {}..{}",self.long,x1,x2);
-            self.constraint = Some(Box::new(
-                move |v| {
-                    let x = v.as_float().unwrap();
-                    if x < x1 || x > x2 {
-                        return error(&msg);
+                Ok(v)
+            }
+        ));
+    }
+
+    pub fn set_choice_constraint(&mut self, choices: Vec<String>) {
+        let quoted: Vec<String> = choices.iter().map(|c| format!("'{}'",c)).collect();
+        let msg = format!("is not one of {}",quoted.join(", "));
+        let long = self.long.clone();
+        self.constraint_desc = format!("one of {}",quoted.join(", "));
+        self.constraint = Some(Box::new(
+            move |v| {
+                if let Value::Str(ref s) = v {
+                    if ! choices.contains(s) {
+                        return lapp_error(format!("flag '{}': '{}' {}",long,s,msg));
                     }
-                    Ok(Value::Float(x))
                 }
-            ));
-        }
-        Ok(())
+                Ok(v)
+            }
+        ));
     }
 
     pub fn set_value(&mut self, v: Value) -> Result<()> {
         if ! self.overriden && self.is_set && ! self.is_multiple {
-            return error(format!("flag already specified {}",self.long));
+            return lapp_error(format!("flag already specified {}",self.long));
+        }
+        if self.is_multiple {
+            let count = self.value.as_array().map(|arr| arr.len()).unwrap_or(0);
+            if let Some(max) = self.max_count {
+                if count >= max {
+                    return self.count_error(count + 1);
+                }
+            }
         }
         self.is_set = true;
+        self.has_value = true;
         if ! self.is_multiple {
             self.value = v;
         } else {
@@ -100,6 +329,16 @@ impl Flag {
         Ok(())
     }
 
+    fn count_error(&self, got: usize) -> Result<()> {
+        let wanted = match (self.min_count, self.max_count) {
+            (Some(lo), Some(hi)) if lo == hi => format!("exactly {}",lo),
+            (Some(lo), _) if got < lo => format!("at least {}",lo),
+            (_, Some(hi)) => format!("at most {}",hi),
+            _ => "more".into()
+        };
+        lapp_error(format!("flag '{}': requires {} values, got {}",self.long,wanted,got))
+    }
+
     pub fn position(&self) -> Option<usize> {
         if self.pos > 0 {Some(self.pos)} else {None}
     }
@@ -113,13 +352,24 @@ impl Flag {
                 if let Type::Arr(_) = self.vtype {
                 } else
                 if ! self.is_multiple {
-                    //~ return error(format!("required flag {}",self.long));
+                    //~ return lapp_error(format!("required flag {}",self.long));
                 }
             } else {
-                self.value = self.defval.clone();
+                self.value = match (&self.vtype, &self.defval) {
+                    (Type::FileInLines, Value::FileIn(file)) => self.vtype.parse_string(file)?,
+                    _ => self.defval.clone()
+                };
                 self.strings.push(self.defstr.clone());
             }
         }
+        if let Some(min) = self.min_count {
+            let count = self.value.as_array().map(|arr| arr.len()).unwrap_or(0);
+            if count < min {
+                return self.count_error(count);
+            }
+        }
+        // multiple flags always have a value, even if only an empty array
+        self.has_value = self.is_set || ! self.value.is_none();
         Ok(())
     }
 
@@ -130,34 +380,113 @@ impl Flag {
 
     pub fn clear(&mut self) {
         self.is_set = false;
+        self.has_value = false;
+        self.overriden = false;
         self.strings.clear();
-        self.value = Value::None;
+        self.defval = self.original_defval.clone();
+        self.defstr = self.original_defstr.clone();
+        // multiple values are pushed onto an array
+        self.value = if self.is_multiple {Value::empty_array()} else {Value::None};
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.defval.is_none() && ! self.is_multiple && ! self.is_optional && self.vtype != Type::Bool
+            && self.default_from.is_none()
+    }
+
+    // the flag as written in help, like '--verbose, -v' or '<file>'
+    pub fn display_name(&self) -> String {
+        if self.pos > 0 {
+            format!("<{}>",self.long)
+        } else if self.short != '\0' && self.long != self.short.to_string() {
+            format!("--{}, -{}",self.long,self.short)
+        } else if self.short != '\0' {
+            format!("-{}",self.short)
+        } else {
+            format!("--{}",self.long)
+        }
+    }
+
+    // the name, type and description columns of a help table,
+    // e.g. ("--verbose, -v", "bool", "verbose flag")
+    pub fn help_columns(&self) -> (String, String, String) {
+        let mut vtype = self.vtype.short_name();
+        if self.is_multiple {
+            vtype += "...";
+        }
+        if ! self.constraint_desc.is_empty() {
+            vtype += &format!(" ({})",self.constraint_desc);
+        }
+        (self.display_name(), vtype, self.help.clone())
+    }
+
+    // how the flag appears in a usage line, e.g. '--output OUTPUT' or '[<file>]'
+    pub fn usage_repr(&self) -> Option<String> {
+        if self.synopsis_hidden {
+            return None;
+        }
+        let dots = if self.is_multiple {"..."} else {""};
+        let repr = if self.pos > 0 {
+            format!("<{}>{}",self.long,dots)
+        } else {
+            // prefer the long form
+            let name = if self.long.chars().count() > 1 {
+                format!("--{}",self.long)
+            } else {
+                format!("-{}",self.long)
+            };
+            if self.vtype == Type::Bool {
+                name
+            } else {
+                let meta = self.long.to_uppercase().replace('-',"_");
+                format!("{} {}{}",name,meta,dots)
+            }
+        };
+        Some(if self.is_required() {
+            repr
+        } else {
+            format!("[{}]",repr)
+        })
     }
 
     pub fn rust_name(&self) -> String {
         // long name may need massaging to become a Rust variable name
         // The result must be snake_case to keep compiler happy!
-        let mut name = self.long.replace('-',"_").to_lowercase().to_string();
+        // Anything that can't be in an identifier becomes '_'
+        let mut name: String = self.long.to_lowercase().chars()
+            .map(|c| if c.is_alphanumeric() {c} else {'_'})
+            .collect();
         let firstc = name.chars().nth(0).unwrap();
-        if firstc.is_digit(10) || firstc == '_' {
+        if firstc.is_numeric() || firstc == '_' {
             name = format!("c_{}",name);
         }
         name
     }
 
+    // the type of each value, looking inside arrays
+    pub fn effective_type(&self) -> &Type {
+        let mut t = &self.vtype;
+        while let Some(inner) = t.array_type() {
+            t = inner;
+        }
+        t
+    }
+
     pub fn rust_type(&self) -> String {
         self.vtype.rust_name(self.is_multiple)
     }
 
     pub fn getter_name(&self) -> String {
-        let mut tname = self.vtype.short_name();
+        if self.vtype == Type::KeyVal {
+            return format!("args.get_string_map(\"{}\")",self.long);
+        }
+        if self.vtype == Type::FileInLines {
+            return format!("args.get_strings(\"{}\")",self.long);
+        }
+        let mut tname = self.effective_type().short_name();
         // Is this an array flag? Two possibilities - the type is an array,
         // or our multiple flag is set.
-        let maybe_array = self.vtype.array_type();
-        if maybe_array.is_some() {
-            tname = maybe_array.unwrap().short_name() + "s";
-        } else
-        if self.is_multiple {
+        if self.vtype.array_type().is_some() || self.is_multiple {
             tname.push('s');
         }
         format!("args.get_{}(\"{}\")",tname,self.long)
@@ -166,3 +495,14 @@ impl Flag {
 
 
 }
+
+// None if the value is not the same kind of number as the bounds
+fn in_range(v: &Value, lo: &Value, hi: &Value) -> Option<bool> {
+    match (v, lo, hi) {
+        (&Value::Int(n), &Value::Int(lo), &Value::Int(hi)) => Some(n >= lo && n <= hi),
+        (&Value::Float(x), &Value::Float(lo), &Value::Float(hi)) => Some(x >= lo && x <= hi),
+        (&Value::I64(n), &Value::I64(lo), &Value::I64(hi)) => Some(n >= lo && n <= hi),
+        (&Value::F64(x), &Value::F64(lo), &Value::F64(hi)) => Some(x >= lo && x <= hi),
+        _ => None
+    }
+}
@@ -0,0 +1,45 @@
+// Writing flag values as JSON, without needing serde
+
+use std::fmt::Write;
+use types::*;
+
+pub fn quote(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            '\r' => res += "\\r",
+            '\t' => res += "\\t",
+            c if (c as u32) < 0x20 => { write!(res,"\\u{:04x}",c as u32).unwrap(); },
+            c => res.push(c)
+        }
+    }
+    res.push('"');
+    res
+}
+
+pub fn value(v: &Value) -> String {
+    match *v {
+        Value::Str(ref s) | Value::FileIn(ref s) | Value::FileOut(ref s) => quote(s),
+        Value::Path(ref p) => quote(&p.to_string_lossy()),
+        #[cfg(feature = "regex")]
+        Value::Regex(ref r) => quote(r.as_str()),
+        Value::Int(n) => n.to_string(),
+        Value::U32(n) => n.to_string(),
+        Value::U64(n) => n.to_string(),
+        Value::USize(n) => n.to_string(),
+        Value::I64(n) => n.to_string(),
+        // JSON has no NaN or infinity
+        Value::Float(x) => if x.is_finite() { x.to_string() } else { "null".into() },
+        Value::F64(x) => if x.is_finite() { x.to_string() } else { "null".into() },
+        Value::Bool(b) => b.to_string(),
+        Value::Arr(ref arr) => {
+            let items: Vec<String> = arr.iter().map(|v| value(v)).collect();
+            format!("[{}]",items.join(","))
+        },
+        Value::None | Value::Error(_) => "null".into()
+    }
+}
@@ -36,7 +36,12 @@
 //! flag; its value will be a vector. This vector may be empty (flag is not required).
 //! If the '...' appears inside the type specifier (e.g. '-p (integer...)') then
 //! the flag is expecting several space-separated values (like -p '10 20 30'); it is also
-//! represented by a vector.
+//! represented by a vector. A multiple flag may limit how many values it takes
+//! with 'min N', 'max N' or 'exactly N' after the type, e.g. '--point... (float exactly 2)'.
+//! Likewise, '(integer... min-length 2)' needs at least two space-separated values.
+//!
+//! A positional argument of type 'raw' collects all arguments after the other positional
+//! arguments as strings, without treating any of them as flags, e.g. '<rest> (raw)'.
 //!
 //! Rest of line (or any other kind of line) is ignored.
 //!
@@ -47,32 +52,126 @@
 use std::process;
 use std::env;
 use std::io;
-use std::io::{Write,Read};
-use std::error::Error;
+use std::io::{Write,Read,BufRead,IsTerminal};
 use std::str::FromStr;
+use std::convert::TryFrom;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path,PathBuf};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+
+#[cfg(feature = "url-type")]
+extern crate url;
+#[cfg(feature = "toml-spec")]
+extern crate toml;
+#[cfg(feature = "glob")]
+extern crate glob;
+#[cfg(feature = "regex")]
+extern crate regex;
+#[cfg(feature = "derive")]
+extern crate lapp_derive;
+#[cfg(feature = "password-prompt")]
+extern crate rpassword;
 
+#[macro_use]
+mod macros;
 mod strutil;
 mod types;
 mod flag;
-use types::*;
+mod spec_writer;
+mod json;
+mod arg_spec;
+#[cfg(feature = "toml-spec")]
+mod toml_spec;
+#[cfg(feature = "build")]
+pub mod build;
+pub use types::{Value,Type,LappError};
+/// the error result used throughout, for custom conversions like `get_map_result`
+pub use types::lapp_error as make_error;
+use types::lapp_error;
+pub use types::LappError as Error;
 pub type Result<T> = types::Result<T>;
 use flag::Flag;
+pub use flag::FlagInfo;
+pub use spec_writer::SpecWriter;
+pub use strutil::shell_escape;
+pub use arg_spec::ArgSpec;
+#[cfg(feature = "derive")]
+pub use lapp_derive::Args;
 
 pub struct Args<'a> {
     flags: Vec<Flag>,
     pos: usize,
-    text: &'a str,
+    text: Cow<'a,str>,
     varargs: bool,
     user_types: Vec<String>,
     istart: usize,
+    permissive: bool,
+    unknown: Vec<String>,
+    remaining: Vec<String>,
+    program_name: Option<String>,
+    // current '[group: Name]' while parsing the spec
+    group: String,
+    // flags tied together with '(exactly-one-of NAME)'
+    exactly_one: HashMap<String,Vec<String>>,
+    // the next positional argument to fill in
+    arg_pos: usize,
+    // track which flags are read, and complain about the others
+    warn_unused: bool,
+    // shown after the spec text in help
+    extra_help: Vec<String>,
+    // where the spec was read from, for error messages
+    spec_file: Option<PathBuf>,
+    // '--help NAME' shows just one section
+    help_section: Option<String>,
 }
 
 impl <'a> Args<'a> {
     /// provide a _usage string_ from which we extract flag definitions
     pub fn new(text: &'a str) -> Args {
-        Args{flags: Vec::new(), pos: 0, text: text, varargs: false, user_types: Vec::new(), istart: 1}
+        Args{flags: Vec::new(), pos: 0, text: Cow::Borrowed(text), varargs: false, user_types: Vec::new(), istart: 1,
+            permissive: false, unknown: Vec::new(), remaining: Vec::new(), program_name: None, group: String::new(), exactly_one: HashMap::new(),
+            arg_pos: 1, warn_unused: false, extra_help: Vec::new(),
+            spec_file: None, help_section: None}
+    }
+
+    /// read the flag definitions from a TOML table instead of a usage string.
+    /// The equivalent usage text is generated for help.
+    #[cfg(feature = "toml-spec")]
+    pub fn from_toml(s: &str) -> Result<Args<'static>> {
+        let mut args = Args::new("");
+        args.text = Cow::Owned(toml_spec::spec_from_toml(s)?);
+        args.parse_spec()?;
+        Ok(args)
+    }
+
+    /// build the flags from a list of `ArgSpec` definitions.
+    /// The equivalent usage text is generated for help.
+    pub fn from_specs(specs: &[ArgSpec]) -> Args<'static> {
+        let mut args = Args::new("");
+        let text: Vec<String> = specs.iter().map(|s| s.spec_line()).collect();
+        args.text = Cow::Owned(text.join("\n") + "\n");
+        if let Err(e) = args.parse_spec() {
+            args.quit(&e.0);
+        }
+        args
+    }
+
+    /// add a flag defined by an `ArgSpec` to an existing spec
+    pub fn merge_spec(&mut self, spec: &ArgSpec) -> Result<()> {
+        if ! self.spec_parsed() {
+            self.parse_spec()?;
+        }
+        let line = spec.spec_line();
+        self.parse_spec_line(&line)?;
+        let text = self.text.to_mut();
+        if ! text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&line);
+        text.push('\n');
+        Ok(())
     }
 
     /// start offset in program command-line arguments.
@@ -83,6 +182,13 @@ impl <'a> Args<'a> {
         self
     }
 
+    /// program name to use in error messages, instead of
+    /// the name of the executable
+    pub fn with_program_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.program_name = Some(name.into());
+        self
+    }
+
     /// declare any user-defined types to be used in the spec.
     /// (They will need to implement FromStr)
     pub fn user_types(&mut self, types: &[&str]) {
@@ -90,13 +196,276 @@ impl <'a> Args<'a> {
         self.user_types = v;
     }
 
+    /// collect unknown flags and extra arguments instead of failing.
+    /// (See `unrecognized_flags` and `remaining_args`)
+    pub fn set_permissive(&mut self, yes: bool) {
+        self.permissive = yes;
+    }
+
+    /// in strict mode (the default) unknown flags are errors.
+    /// Otherwise the same as `set_permissive(true)`
+    pub fn check_unknown_flags(mut self, strict: bool) -> Self {
+        self.permissive = ! strict;
+        self
+    }
+
+    /// keep track of which flags are read with `get_*`; any flags never read
+    /// are reported on stderr when `Args` is dropped (see `report_unused`)
+    pub fn set_warn_unused(&mut self, warn: bool) {
+        self.warn_unused = warn;
+    }
+
+    /// flags which have not been read since parsing, in warn-unused mode.
+    /// The help flag is never included.
+    pub fn unused_flags(&self) -> Vec<&str> {
+        if ! self.warn_unused {
+            return Vec::new();
+        }
+        self.flags.iter()
+            .filter(|f| ! f.accessed.get() && f.long != "help")
+            .map(|f| f.long.as_str())
+            .collect()
+    }
+
+    /// write a warning to stderr for each flag which was never read
+    pub fn report_unused(&self) {
+        for name in self.unused_flags() {
+            eprintln!("warning: flag '{}' is declared but never used",name);
+        }
+    }
+
+    /// unknown flags found in permissive mode
+    pub fn unrecognized_flags(&self) -> &[String] {
+        &self.unknown
+    }
+
+    /// arguments beyond the declared positional arguments in permissive mode,
+    /// or all the arguments after a subcommand
+    pub fn remaining_args(&self) -> &[String] {
+        &self.remaining
+    }
+
+    /// all the flag values as a JSON object, keyed by long name.
+    /// Missing values are null.
+    pub fn get_as_json(&self) -> String {
+        let items: Vec<String> = self.flags.iter()
+            .map(|f| if f.vtype == Type::Password && f.is_set {
+                format!("{}:{}",json::quote(&f.long),json::quote("***"))
+            } else {
+                format!("{}:{}",json::quote(&f.long),json::value(&f.value))
+            })
+            .collect();
+        format!("{{{}}}",items.join(","))
+    }
+
+    /// change the default of a flag, which must be of the right type.
+    /// Does nothing if the flag is unknown or has been given explicitly.
+    pub fn set_default(&mut self, name: &str, value: &str) -> Result<()> {
+        match self.flags_by_long(name) {
+            Ok(ref mut flag) if ! flag.is_set => flag.set_default(value),
+            _ => Ok(())
+        }
+    }
+
+    /// use values from a config file as defaults, keyed by long name.
+    /// Flags given on the command-line win over these, which win over the spec defaults.
+    /// Best called before parsing the command-line, so that required flags can be filled in.
+    /// Returns the names which are not flags, in sorted order, so the caller can decide
+    /// whether they are errors.
+    pub fn merge_defaults_from_hashmap(&mut self, map: &HashMap<String,String>) -> Result<Vec<String>> {
+        if ! self.spec_parsed() {
+            self.parse_spec()?;
+        }
+        let mut names: Vec<_> = map.keys().collect();
+        names.sort();
+        let mut unknown = Vec::new();
+        for name in names {
+            let value = &map[name];
+            if self.flags_by_long_ref(name).is_err() {
+                unknown.push(name.clone());
+                continue;
+            }
+            let flag = self.flags_by_long(name)?;
+            if flag.is_set {
+                continue;
+            }
+            // values must match the flag's type exactly, like set_default
+            flag.set_default(value)?;
+        }
+        Ok(unknown)
+    }
+
+    /// the type of a flag
+    pub fn get_flag_type(&self, name: &str) -> Result<&Type> {
+        Ok(&self.flags_by_long_ref(name)?.vtype)
+    }
+
+    /// position of a positional argument (starting at 1), or None for other flags
+    pub fn get_flag_position_result(&self, name: &str) -> Result<Option<usize>> {
+        Ok(self.flags_by_long_ref(name)?.position())
+    }
+
+    /// position of a positional argument, quitting if the flag is unknown
+    pub fn get_flag_position(&self, name: &str) -> Option<usize> {
+        self.unwrap(self.get_flag_position_result(name))
+    }
+
+    /// is this a positional argument? Quits if the flag is unknown
+    pub fn is_positional(&self, name: &str) -> bool {
+        self.get_flag_position(name).is_some()
+    }
+
+    /// can this flag occur multiple times?
+    pub fn is_flag_multiple(&self, name: &str) -> Result<bool> {
+        Ok(self.flags_by_long_ref(name)?.is_multiple)
+    }
+
+    /// iterate over the flag definitions
+    pub fn flags_iter<'b>(&'b self) -> impl Iterator<Item=FlagInfo<'b>> + 'b {
+        self.flags.iter().map(|f| f.info())
+    }
+
+    /// the flags in each '[group: Name]' section, in order.
+    /// Flags before the first group are in the section named "".
+    pub fn help_sections(&self) -> Vec<(String, Vec<FlagInfo<'_>>)> {
+        let mut res: Vec<(String, Vec<FlagInfo>)> = Vec::new();
+        for f in &self.flags {
+            match res.iter().position(|s| s.0 == f.group) {
+                Some(i) => res[i].1.push(f.info()),
+                None => res.push((f.group.clone(), vec![f.info()]))
+            }
+        }
+        res
+    }
+
+    /// long names of all the flags, in order
+    pub fn names(&self) -> impl Iterator<Item=&str> {
+        self.flags.iter().map(|f| f.long.as_str())
+    }
+
+    /// the short flags which have been declared
+    pub fn short_names(&self) -> impl Iterator<Item=char> + '_ {
+        self.flags.iter().filter(|f| f.short != '\0').map(|f| f.short)
+    }
+
+    /// long names of the flags of a given type, like "integer" or "array of string",
+    /// in alphabetical order. An unknown type name gives no flags.
+    pub fn get_flag_names_of_type(&self, type_name: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.flags.iter()
+            .filter(|f| f.vtype.short_name() == type_name)
+            .map(|f| f.long.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// is there a short flag like '-v'?
+    pub fn is_short_flag(&self, ch: char) -> bool {
+        ch != '\0' && self.flags.iter().any(|f| f.short == ch)
+    }
+
+    /// how many positional arguments are declared
+    pub fn positional_count(&self) -> usize {
+        self.flags.iter().filter(|f| f.pos > 0).count()
+    }
+
+    /// names of the positional arguments, in order
+    pub fn positional_names(&self) -> Vec<&str> {
+        let mut flags: Vec<&Flag> = self.flags.iter().filter(|f| f.pos > 0).collect();
+        flags.sort_by_key(|f| f.pos);
+        flags.iter().map(|f| f.long.as_str()).collect()
+    }
+
+    /// describe a flag in detail: its type, default, constraints and help
+    pub fn describe_flag_result(&self, name: &str) -> Result<String> {
+        let flag = self.flags_by_long_ref(name)?;
+        let mut res = flag.display_name();
+        let mut parts = vec![flag.vtype.short_name()];
+        parts.push(if flag.is_required() {"required"} else {"optional"}.into());
+        if flag.is_multiple {
+            parts.push("multiple".into());
+        }
+        if ! flag.constraint_desc.is_empty() {
+            parts.push(flag.constraint_desc.clone());
+        }
+        match flag.defval {
+            Value::None => {},
+            Value::Str(ref s) => parts.push(format!("default: {:?}",s)),
+            Value::Arr(_) => {},
+            Value::Bool(b) => parts.push(format!("default: {}",b)),
+            _ => parts.push(format!("default: {}",flag.defstr))
+        }
+        res += &format!("  [{}]\n",parts.join(", "));
+        if ! flag.help.is_empty() {
+            res += &format!("  {}\n",flag.help);
+        }
+        Ok(res)
+    }
+
+    /// describe a flag in detail, quitting if it's unknown
+    pub fn describe_flag(&self, name: &str) -> String {
+        self.unwrap(self.describe_flag_result(name))
+    }
+
+    /// a one-line summary of the flags and arguments,
+    /// like `prog [--verbose] --output OUTPUT <file>`
+    pub fn usage_line(&self) -> String {
+        let mut flags: Vec<&Flag> = self.flags.iter().filter(|f| f.long != "help").collect();
+        // positional arguments come last, in order
+        flags.sort_by_key(|f| f.pos);
+        let mut res = self.program_name();
+        for repr in flags.iter().filter_map(|f| f.usage_repr()) {
+            res.push(' ');
+            res += &repr;
+        }
+        res
+    }
+
+    /// the description at the start of the spec, followed by the usage line,
+    /// like "File input and output\nUsage: prog [--verbose] <in>"
+    pub fn short_usage(&self) -> String {
+        let mut description = Vec::new();
+        for line in self.text.lines() {
+            let line = line.trim();
+            if line.starts_with('-') || line.starts_with('<') || line.starts_with('[') || line.starts_with('@') {
+                break;
+            }
+            if line.is_empty() {
+                if description.is_empty() { continue; } else { break; }
+            }
+            description.push(line);
+        }
+        let usage = format!("Usage: {}",self.usage_line());
+        if description.is_empty() {
+            usage
+        } else {
+            format!("{}\n{}",description.join("\n"),usage)
+        }
+    }
+
+    /// names of flags which must be given, in alphabetical order
+    pub fn required_flags(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.flags.iter()
+            .filter(|f| f.is_required())
+            .map(|f| f.long.clone()).collect();
+        res.sort();
+        res
+    }
+
+    /// names of flags which may be left out, in alphabetical order
+    pub fn optional_flags(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.flags.iter()
+            .filter(|f| ! f.is_required())
+            .map(|f| f.long.clone()).collect();
+        res.sort();
+        res
+    }
+
     /// bail out of program with non-zero return code.
     /// May force this to panic instead with the
     /// LAPP_PANIC environment variable.
     pub fn quit(&self, msg: &str) -> ! {
-        let path = env::current_exe().unwrap();
-        let exe = path.file_name().unwrap().to_string_lossy();
-        let text = format!("{} error: {}\nType {} --help for more information",exe,msg,exe);
+        let text = self.quit_message(msg);
         if env::var("LAPP_PANIC").is_ok() {
             panic!(text);
         } else {
@@ -105,17 +474,42 @@ impl <'a> Args<'a> {
         }
     }
 
+    fn program_name(&self) -> String {
+        match self.program_name {
+            Some(ref name) => name.clone(),
+            None => {
+                let path = env::current_exe().unwrap();
+                path.file_name().unwrap().to_string_lossy().into_owned()
+            }
+        }
+    }
+
+    fn quit_message(&self, msg: &str) -> String {
+        let exe = self.program_name();
+        format!("{} error: {}\nType {} --help for more information",exe,msg,exe)
+    }
+
     /// create suggested variable or struct declarations for accessing the flags...
     pub fn declarations(&mut self, struct_name: &str) -> String {
-        if let Err(e) = self.parse_spec() {
-            self.quit(e.description());
+        if ! self.spec_parsed() {
+            if let Err(e) = self.parse_spec() {
+                self.quit(&e.0);
+            }
         }
+        // the Rust type doesn't tell us that the value was given in hex
+        let note = |f: &Flag| {
+            match *f.effective_type() {
+                Type::Hex => " /* hex */".to_string(),
+                Type::Radix(base) => format!(" /* base {} */",base),
+                _ => String::new()
+            }
+        };
         let mut res = String::new();
         if struct_name.len() > 0 {
             res += &format!("const USAGE: &'static str = \"\n{}\";\n",self.text);
             res += &format!("#[derive(Debug)]\nstruct {} {{\n",struct_name);
             for f in &self.flags {
-                res += &format!("\t{}: {},\n",f.rust_name(),f.rust_type());
+                res += &format!("\t{}: {},{}\n",f.rust_name(),f.rust_type(),note(f));
             }
             res += &format!(
                 "}}\n\nimpl {} {{\n\tfn new() -> ({},lapp::Args<'static>) {{\n",
@@ -128,18 +522,195 @@ impl <'a> Args<'a> {
             res += &format!("\t\t}},args)\n\t}}\n}}\n\n");
         } else {
             for f in &self.flags {
-                res += &format!("    let {} = {};\n",
-                    f.rust_name(),f.getter_name());
+                res += &format!("    let {} = {};{}\n",
+                    f.rust_name(),f.getter_name(),note(f));
+            }
+        }
+        res
+    }
+
+    /// a command-line which would give the same values, like
+    /// `["--verbose", "--output", "out.txt", "in.txt"]`.
+    /// Flags left at their defaults are not included.
+    pub fn to_argv(&self) -> Vec<String> {
+        let mut res = Vec::new();
+        let mut positionals: Vec<&Flag> = Vec::new();
+        for f in &self.flags {
+            if f.pos > 0 {
+                positionals.push(f);
+                continue;
+            }
+            let name = if f.long.chars().count() > 1 {
+                format!("--{}",f.long)
+            } else {
+                format!("-{}",f.long)
+            };
+            if f.vtype == Type::Bool {
+                match f.value {
+                    Value::Bool(true) if f.is_set => res.push(name),
+                    // only a flag which is on by default has a '--no-' form
+                    Value::Bool(false) if f.is_set && matches!(f.defval, Value::Bool(true)) =>
+                        res.push(format!("--no-{}",f.long)),
+                    _ => {}
+                }
+            } else if f.is_set {
+                for s in &f.strings {
+                    res.push(name.clone());
+                    res.push(s.clone());
+                }
+            } else if f.has_value && f.defstr != f.original_defstr {
+                // a default set with set_default
+                res.push(name);
+                res.push(f.defstr.clone());
+            }
+        }
+        positionals.sort_by_key(|f| f.pos);
+        let values: Vec<&String> = positionals.iter()
+            .filter(|f| f.is_set)
+            .flat_map(|f| f.strings.iter())
+            .collect();
+        // positional values which look like flags must follow '--'
+        if values.iter().any(|s| s.starts_with('-')) {
+            res.push("--".into());
+        }
+        res.extend(values.into_iter().cloned());
+        res
+    }
+
+    /// like `to_argv`, but as one string which can be passed to a shell.
+    /// Every argument is quoted with `shell_escape`.
+    pub fn to_shell_command(&self) -> String {
+        let argv: Vec<String> = self.to_argv().iter().map(|s| shell_escape(s)).collect();
+        argv.join(" ")
+    }
+
+    /// write the help text, as shown by `--help`, followed by the usage line
+    pub fn print_help<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w,"{}",strutil::dedent(&self.text).trim_end())?;
+        writeln!(w)?;
+        writeln!(w,"Usage: {}",self.usage_line())?;
+        if ! self.extra_help.is_empty() {
+            writeln!(w)?;
+        }
+        for text in &self.extra_help {
+            writeln!(w,"{}",text)?;
+        }
+        Ok(())
+    }
+
+    /// add some text to the help, after the spec. This accumulates,
+    /// so it can be called for each piece of information, like a list of plugins.
+    pub fn set_help_text(&mut self, text: &str) {
+        self.extra_help.push(text.into());
+    }
+
+    /// help generated from the flags rather than the spec text, as aligned
+    /// columns of name, type and description.
+    pub fn help_table(&self) -> String {
+        Args::format_help_table(self.flags.iter())
+    }
+
+    fn format_help_table<'f, I: Iterator<Item=&'f Flag>>(flags: I) -> String {
+        let rows: Vec<_> = flags.map(|f| f.help_columns()).collect();
+        let name_width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+        let type_width = rows.iter().map(|r| r.1.len()).max().unwrap_or(0);
+        let mut res = String::new();
+        for (name, vtype, help) in rows {
+            let line = format!("  {:nw$}  {:tw$}  {}",name,vtype,help,nw=name_width,tw=type_width);
+            res += line.trim_end();
+            res.push('\n');
+        }
+        res
+    }
+
+    // names of the '[group: Name]' sections, in order
+    fn group_names(&self) -> Vec<&str> {
+        let mut res: Vec<&str> = Vec::new();
+        for f in &self.flags {
+            if ! f.group.is_empty() && ! res.contains(&f.group.as_str()) {
+                res.push(&f.group);
             }
         }
         res
     }
 
+    /// write out help for just one '[group: Name]' section, as in `help_table`.
+    /// If there is no such section, the available sections are listed.
+    pub fn print_help_section<W: Write>(&self, w: &mut W, name: &str) -> io::Result<()> {
+        let groups = self.group_names();
+        if groups.contains(&name) {
+            writeln!(w,"{}:",name)?;
+            write!(w,"{}",Args::format_help_table(self.flags.iter().filter(|f| f.group == name)))
+        } else if groups.is_empty() {
+            writeln!(w,"no help section '{}'; there are no sections",name)
+        } else {
+            writeln!(w,"no help section '{}'; sections are: {}",name,groups.join(", "))
+        }
+    }
+
+    /// parse, and print out the flag values
     pub fn dump(&mut self) {
         self.parse();
+        self.dump_to_writer(&mut io::stdout()).expect("cannot write to stdout");
+    }
+
+    /// write out the flag values in order, one per line,
+    /// like `  --<long>  <type>  <value>` with the value in debug form.
+    pub fn dump_to_writer<W: Write>(&self, w: &mut W) -> io::Result<()> {
         for f in &self.flags {
-            println!("flag '{}' value {:?}",f.long,f.value);
+            if f.vtype == Type::Password && f.is_set {
+                // never show a password
+                writeln!(w,"  --{}  {}  Str(\"***\")",f.long,f.vtype.short_name())?;
+                continue;
+            }
+            writeln!(w,"  --{}  {}  {:?}",f.long,f.vtype.short_name(),f.value)?;
+        }
+        Ok(())
+    }
+
+    fn env_var_name(prefix: &str, name: &str) -> String {
+        format!("{}_{}",prefix,name).to_uppercase().chars()
+            .map(|c| if c.is_alphanumeric() {c} else {'_'})
+            .collect()
+    }
+
+    /// set the flags from environment variables instead of the command-line.
+    /// Each flag is read from a variable like MYAPP_OUTPUT, where the prefix is the
+    /// program name (see `with_program_name`). It is an error if a required
+    /// flag's variable is not set.
+    pub fn from_env_only(&mut self) -> Result<()> {
+        if ! self.spec_parsed() {
+            self.parse_spec()?;
+        }
+        let prefix = self.program_name();
+        for flag in &mut self.flags {
+            let var = Args::env_var_name(&prefix,&flag.long);
+            if let Ok(val) = env::var(&var) {
+                if flag.vtype == Type::Bool {
+                    // an empty variable counts as false
+                    let v = if val.is_empty() {
+                        Value::Bool(false)
+                    } else {
+                        Type::BoolString.parse_string(&val)?
+                    };
+                    if let Value::Error(_) = v {
+                        return lapp_error(format!("{}: '{}' is not a boolean",var,val));
+                    }
+                    flag.set_value(v)?;
+                } else if flag.is_multiple {
+                    let parts = strutil::tokenize(&val)?;
+                    let parts: Vec<&str> = parts.iter().map(|s| s.as_str()).collect();
+                    flag.set_value_from_strings(&parts)?;
+                } else {
+                    flag.set_value_from_string(&val)?;
+                }
+            }
+            flag.check()?;
+            if ! flag.has_value && ! flag.is_optional && flag.long != "help" && flag.default_from.is_none() {
+                return lapp_error(format!("required environment variable {} is not set",var));
+            }
         }
+        self.resolve_default_from()
     }
 
     pub fn parse_env_args(&mut self) -> Result<()> {
@@ -149,7 +720,9 @@ impl <'a> Args<'a> {
 
     /// parse the spec and the command-line
     pub fn parse_result(&mut self) -> Result<()> {
-        self.parse_spec()?;
+        if ! self.spec_parsed() {
+            self.parse_spec()?;
+        }
         self.parse_env_args()
     }
 
@@ -157,43 +730,220 @@ impl <'a> Args<'a> {
     /// parse the spec and the command-line, quitting on error.
     pub fn parse(&mut self) {
         if let Err(e) = self.parse_result() {
-            self.quit(e.description());
+            self.quit(&e.0);
+        }
+    }
+
+    /// parse the spec and the given arguments, instead of the command-line.
+    /// Handy for tests: `args.try_parse_from(["--verbose", "file.txt"])`
+    pub fn try_parse_from<I>(&mut self, args: I) -> Result<()>
+    where I: IntoIterator, I::Item: Into<String> {
+        if ! self.spec_parsed() {
+            self.parse_spec()?;
+        }
+        self.parse_command_line(args.into_iter().map(Into::into).collect())
+    }
+
+    /// parse the spec and the given arguments, quitting on error.
+    pub fn parse_from<I>(&mut self, args: I)
+    where I: IntoIterator, I::Item: Into<String> {
+        if let Err(e) = self.try_parse_from(args) {
+            self.quit(&e.0);
         }
     }
 
+    /// parse the arguments of a subcommand against its own spec
+    pub fn parse_subcommand(spec: &'a str, args: &[String]) -> Result<Args<'a>> {
+        let mut sub = Args::new(spec);
+        sub.try_parse_from(args.iter().cloned())?;
+        Ok(sub)
+    }
+
+    /// parse the arguments after a '(string subcommand)' positional against
+    /// the spec for that command. Its program name is like 'mytool commit'.
+    pub fn subcommand_args_result<'s>(&self, cmd: &str, spec: &'s str) -> Result<Args<'s>> {
+        let mut sub = Args::parse_subcommand(spec,&self.remaining)?;
+        sub.program_name = Some(format!("{} {}",self.program_name(),cmd));
+        Ok(sub)
+    }
+
+    /// parse the arguments of a subcommand, quitting on error
+    pub fn subcommand_args<'s>(&self, cmd: &str, spec: &'s str) -> Args<'s> {
+        self.unwrap(self.subcommand_args_result(cmd,spec))
+    }
+
+    /// parse the spec and the command-line, quitting on error,
+    /// and hand back the `Args`. Useful for chaining:
+    /// `Args::new(SPEC).with_program_name("mytool").parse_owned()`
+    pub fn parse_owned(mut self) -> Self {
+        self.parse();
+        self
+    }
+
     /// parse the spec and create the flags.
     pub fn parse_spec(&mut self) -> Result<()> {
-        for line in self.text.lines() {
+        self.parse_spec_with_context(Path::new(""))
+    }
+
+    /// parse the spec and create the flags, where any
+    /// '@include "file"' lines are read relative to `base`.
+    pub fn parse_spec_with_context(&mut self, base: &Path) -> Result<()> {
+        match self.validate_spec_with_context(base).into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
+    }
+
+    /// read the spec from a file and parse it. Any '@include' lines are
+    /// read relative to the file, and errors give the file and line number.
+    pub fn parse_spec_from_file(&mut self, path: &Path) -> Result<()> {
+        let txt = match fs::read_to_string(path) {
+            Ok(txt) => txt,
+            Err(e) => return lapp_error(format!("cannot read spec file '{}': {}",path.display(),e))
+        };
+        self.text = Cow::Owned(txt);
+        self.spec_file = Some(path.to_path_buf());
+        self.parse_spec_with_context(path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// parse the spec, carrying on after errors so they can all be reported.
+    /// The result is empty if the spec is fine.
+    pub fn parse_spec_and_validate(&mut self) -> Vec<LappError> {
+        self.validate_spec_with_context(Path::new(""))
+    }
+
+    /// like `parse_spec_and_validate`, reading includes relative to `base`
+    pub fn validate_spec_with_context(&mut self, base: &Path) -> Vec<LappError> {
+        let mut errors = Vec::new();
+        match expand_includes(&self.text,base,0) {
+            Ok(Some(text)) => self.text = Cow::Owned(text),
+            Ok(None) => {},
+            Err(e) => {
+                errors.push(e);
+                return errors;
+            }
+        }
+        let text = self.text.clone();
+        for (i, line) in text.lines().enumerate() {
             if let Err(e) = self.parse_spec_line(line) {
-                return error(format!("{}\nat line: '{}'",e.description(),line));
+                let msg = match self.spec_file {
+                    Some(ref file) => format!("spec file '{}', line {}: {}",file.display(),i+1,e),
+                    None => format!("{}\nat line: '{}'",e,line)
+                };
+                errors.push(LappError(msg));
             }
         }
         if let Err(_) = self.flags_by_long("help") {
-            self.parse_spec_line("   -h,--help this help").unwrap();
+            self.group.clear();
+            // '-h' may already mean something else
+            let help = if self.flags.iter().any(|f| f.short == 'h') {
+                "   --help this help"
+            } else {
+                "   -h,--help this help"
+            };
+            self.parse_spec_line(help).unwrap();
+        }
+        if let Err(e) = self.check_default_from() {
+            errors.push(e);
+        }
+        errors
+    }
+
+    // follow the 'default_from' references of a flag, returning the flag
+    // which finally provides the value
+    fn default_from_source<'f>(&'f self, flag: &'f Flag) -> Result<&'f Flag> {
+        let mut current = flag;
+        let mut chain = vec![flag.long.as_str()];
+        while let Some(ref other) = current.default_from {
+            let next = match self.flags_by_long_ref(other) {
+                Ok(next) => next,
+                Err(_) => return lapp_error(format!("flag '{}': default_from unknown flag '{}'",current.long,other))
+            };
+            if next.vtype != current.vtype {
+                return lapp_error(format!("flag '{}': default_from '{}' which is {}, not {}",
+                    current.long,other,next.vtype.short_name(),current.vtype.short_name()));
+            }
+            if chain.contains(&next.long.as_str()) {
+                chain.push(&next.long);
+                return lapp_error(format!("default_from cycle: {}",chain.join(" -> ")));
+            }
+            chain.push(&next.long);
+            current = next;
+        }
+        Ok(current)
+    }
+
+    fn check_default_from(&self) -> Result<()> {
+        for flag in &self.flags {
+            self.default_from_source(flag)?;
+        }
+        Ok(())
+    }
+
+    // flags not given take their value from the flag named by 'default_from'.
+    // The spec has already been checked for cycles.
+    fn resolve_default_from(&mut self) -> Result<()> {
+        let mut updates = Vec::new();
+        for (i, flag) in self.flags.iter().enumerate() {
+            if flag.default_from.is_some() && ! flag.is_set {
+                // the first flag along the chain which was given wins
+                let mut source = flag;
+                while ! source.is_set {
+                    match source.default_from {
+                        Some(ref other) => source = self.flags_by_long_ref(other)?,
+                        None => break
+                    }
+                }
+                if source.has_value {
+                    updates.push((i, source.value.clone(), source.strings.clone()));
+                }
+            }
+        }
+        for (i, value, strings) in updates {
+            let flag = &mut self.flags[i];
+            flag.value = value;
+            flag.strings = strings;
+            flag.has_value = true;
         }
         Ok(())
     }
 
+    // the help flag is always defined once the spec has been parsed
+    fn spec_parsed(&self) -> bool {
+        ! self.flags.is_empty()
+    }
 
     fn parse_spec_line(&mut self, mut slice: &str) -> Result<()> {
         use strutil::*;
         fn flag_error (flag: &Flag,msg: &str) -> Result<()> {
-            error(format!("{}: flag '{}'",msg,flag.long))
+            lapp_error(format!("{}: flag '{}'",msg,flag.long))
         }
 
         if let Some(idx) = slice.find(|c: char| ! c.is_whitespace()) {
             let mut flag: Flag = Default::default();
             let mut is_positional = false;
             slice = &slice[idx..];
+            // '[group: Name]' starts a new section of flags
+            if starts_with(&mut slice,"[group:") {
+                self.group = grab_upto(&mut slice,"]")?;
+                return Ok(());
+            }
             let is_flag = starts_with(&mut slice,"-");
             let mut long_flag = starts_with(&mut slice,"-");
+            if long_flag && slice.starts_with('-') {
+                let name = slice.trim_start_matches('-').split_whitespace().next().unwrap_or("");
+                return lapp_error(format!("spec error: three dashes found; did you mean '--{}'?",name));
+            }
             if is_flag && ! long_flag { // short flag
-                flag.short = (&slice[0..1]).chars().next().unwrap();
+                flag.short = match slice.chars().next() {
+                    Some(ch) => ch,
+                    None => return flag_error(&flag,"missing short flag")
+                };
                 flag.long = flag.short.to_string();
                 if ! flag.short.is_alphanumeric() {
                     return flag_error(&flag,"not allowed: only letters or digits in short flags");
                 }
-                slice = &slice[1..];
+                slice = &slice[flag.short.len_utf8()..];
                 if let Some(0) = slice.find(|c: char| c.is_alphanumeric()) {
                    return flag_error(&flag,"short flags should have one character");
                 }
@@ -217,6 +967,8 @@ impl <'a> Args<'a> {
             } else
             if starts_with(&mut slice, "<") { // positional argument
                 flag.long = grab_upto(&mut slice, ">")?;
+                // '<name>?' is short for '(... optional)'
+                flag.is_optional = starts_with(&mut slice, "?");
                 self.pos = self.pos + 1;
                 flag.pos = self.pos;
                 is_positional = true;
@@ -234,34 +986,130 @@ impl <'a> Args<'a> {
                 slice = skipws(slice);
             }
             if starts_with(&mut slice,"(") {
-                let r = grab_upto(&mut slice, ")")?;
+                let r = grab_upto_unquoted(&mut slice, ')')?;
                 let mut rest = r.as_str().trim();
-                let multable = ends_with(&mut rest,"...");
-                if let Some((b1,b2)) = split_with(rest,"..") {
-                    // bounds on a number type
-                    flag.set_range_constraint(b1,b2)?;
-                } else {
-                    // default VALUE or TYPE
-                    if rest.len() == 0 {
-                        return flag_error(&flag,"nothing inside type specifier");
+                let mut multable = false;
+                // default VALUE or TYPE
+                if rest.is_empty() {
+                    return flag_error(&flag,"nothing inside type specifier");
+                }
+                if starts_with(&mut rest,"default ") {
+                    rest = skipws(rest);
+                    // flag type will be deduced
+                    flag.set_default_from_string(rest,true)?;
+                } else if starts_with(&mut rest,"exactly-one-of ") {
+                    // a single name ties together a group of simple flags,
+                    // otherwise the value must be one of the given words
+                    let mut names = tokenize(rest)?;
+                    match names.len() {
+                        0 => return flag_error(&flag,"'exactly-one-of' needs names"),
+                        1 => {
+                            flag.vtype = Type::Bool;
+                            self.exactly_one.entry(names.remove(0)).or_default().push(flag.long.clone());
+                        },
+                        _ => {
+                            flag.vtype = Type::Str;
+                            flag.set_choice_constraint(names);
+                        }
                     }
-                    if starts_with(&mut rest,"default ") {
-                        rest = skipws(rest);
-                        // flag type will be deduced
-                        flag.set_default_from_string(rest,true)?;
+                } else {
+                    let word = grab_word(&mut rest);
+                    let mut name = word.as_str();
+                    multable = ends_with(&mut name,"...");
+                    if let Some((b1,b2)) = split_with(name,"..") {
+                        // bounds on a number type
+                        flag.set_range_constraint(b1,b2)?;
+                    } else if name == "synopsis-hide" {
+                        // on its own, this is a simple flag
+                        flag.vtype = Type::Bool;
+                        flag.synopsis_hidden = true;
+                    } else if name == "positive" || name == "non-negative" {
+                        // on its own, this means an integer
+                        flag.vtype = Type::Int;
+                        flag.set_sign_constraint(name == "positive");
                     } else {
-                        let name = grab_word(&mut rest);
                         // custom types are _internally_ stored as string types,
                         // but we must verify that it is a known type!
-                        flag.vtype = if self.user_types.iter().any(|s| s == name.as_str()) {
+                        flag.vtype = if self.user_types.iter().any(|s| s == name) {
                             Type::Str
                         } else {
-                            Type::from_name(&name)?
+                            Type::from_name(name)?
                         };
+                    }
+                    // modifiers may follow the type; a default always comes last
+                    while ! rest.is_empty() {
                         if starts_with(&mut rest,"default ") {
                             rest = skipws(rest);
                             // flag already has a definite type
                             flag.set_default_from_string(rest,false)?;
+                            break;
+                        }
+                        let modifier = grab_word(&mut rest);
+                        match modifier.as_str() {
+                            "exactly" | "min" | "max" => {
+                                let n = match grab_word(&mut rest).parse::<usize>() {
+                                    Ok(n) => n,
+                                    Err(_) => return flag_error(&flag,&format!("'{}' needs a count",modifier))
+                                };
+                                if modifier != "max" {
+                                    flag.min_count = Some(n);
+                                }
+                                if modifier != "min" {
+                                    flag.max_count = Some(n);
+                                }
+                            },
+                            "min-length" if multable && ! is_positional => {
+                                flag.min_length = match grab_word(&mut rest).parse::<usize>() {
+                                    Ok(n) => Some(n),
+                                    Err(_) => return flag_error(&flag,"'min-length' needs a count")
+                                };
+                            },
+                            "optional" => flag.is_optional = true,
+                            "subcommand" if is_positional && ! multable && flag.vtype == Type::Str =>
+                                flag.subcommand = true,
+                            "synopsis-hide" => flag.synopsis_hidden = true,
+                            // bounds on a number type
+                            range if range.contains("..") && matches!(flag.vtype, Type::Int | Type::Float | Type::I64 | Type::F64) => {
+                                if let Some((b1,b2)) = split_with(range,"..") {
+                                    flag.set_range_constraint(b1,b2)?;
+                                }
+                            },
+                            "positive" | "non-negative" if flag.vtype == Type::Int || flag.vtype == Type::Float =>
+                                flag.set_sign_constraint(modifier == "positive"),
+                            "default-if-present" => {
+                                let value = grab_word(&mut rest);
+                                if value.is_empty() {
+                                    return flag_error(&flag,"'default-if-present' needs a value");
+                                }
+                                flag.set_default_if_present(&value)?;
+                            },
+                            "default_from" => {
+                                let other = grab_word(&mut rest);
+                                let other = other.trim_matches(|c| c == '\'' || c == '"');
+                                if other.is_empty() {
+                                    return flag_error(&flag,"'default_from' needs a flag name");
+                                }
+                                flag.default_from = Some(other.to_string());
+                            },
+                            "hex" if flag.vtype == Type::Int => flag.vtype = Type::Hex,
+                            "base" if flag.vtype == Type::Int => {
+                                flag.vtype = match grab_word(&mut rest).parse::<u32>() {
+                                    Ok(10) => Type::Int,
+                                    Ok(16) => Type::Hex,
+                                    Ok(b) if (2..=36).contains(&b) => Type::Radix(b),
+                                    _ => return flag_error(&flag,"'base' must be between 2 and 36")
+                                };
+                            },
+                            // the matching files become the values of a multiple flag
+                            #[cfg(feature = "glob")]
+                            "glob" | "glob-required" if flag.vtype == Type::Str => {
+                                flag.glob = Some(modifier == "glob-required");
+                                flag.is_multiple = true;
+                                multable = is_positional;
+                            },
+                            #[cfg(feature = "url-type")]
+                            "https" if flag.vtype == Type::Url => flag.set_https_constraint(),
+                            _ => return flag_error(&flag,&format!("unknown modifier '{}'",modifier))
                         }
                     }
                 }
@@ -269,6 +1117,13 @@ impl <'a> Args<'a> {
                 // by a vector (e.g. --ports '8080 8081 8082').
                 // UNLESS it is a positional argument,
                 // where it is considered multiple!
+                // Raw arguments are always collected like this.
+                if flag.vtype == Type::Raw {
+                    if ! is_positional {
+                        return flag_error(&flag,"raw type only allowed for positional arguments");
+                    }
+                    multable = true;
+                }
                 if multable {
                     flag.defval = Value::empty_array();
                     if is_positional {
@@ -278,11 +1133,25 @@ impl <'a> Args<'a> {
                         }
                         self.varargs = true;
                     } else { // i.e the flag type is an array of a basic scalar type
+                        // (an array of arrays is not supported)
+                        if flag.is_multiple {
+                            return flag_error(&flag,"cannot use both '...' suffix and '...' inside type specifier");
+                        }
                         flag.vtype = flag.vtype.create_empty_array();
                     }
                 }
+                // each 'key=value' is a separate value
+                if flag.vtype == Type::KeyVal && ! is_positional {
+                    flag.is_multiple = true;
+                }
+                // '(bool)' is just a simple flag
+                if flag.vtype == Type::Bool && flag.defval.is_none() {
+                    flag.defval = Value::Bool(false);
+                }
                 if flag.is_multiple {
                     flag.value = Value::empty_array();
+                } else if flag.min_count.is_some() || flag.max_count.is_some() {
+                    return flag_error(&flag,"only multiple flags can have a count");
                 }
             } else {
                 flag.defval = Value::Bool(false);
@@ -296,6 +1165,12 @@ impl <'a> Args<'a> {
             if self.flags_by_long_ref(&flag.long).is_ok() {
                 return flag_error(&flag,"already defined");
             }
+            if flag.short != '\0' && self.flags.iter().any(|f| f.short == flag.short) {
+                return flag_error(&flag,&format!("short flag '{}' already defined",flag.short));
+            }
+            flag.group = self.group.clone();
+            flag.original_defval = flag.defval.clone();
+            flag.original_defstr = flag.defstr.clone();
             self.flags.push(flag);
         }
         Ok(())
@@ -326,43 +1201,85 @@ impl <'a> Args<'a> {
             .next().ok_or(LappError(format!("no arg #{}",pos)))
     }
 
+    fn flags_by_pos_ref(&self, pos: usize) -> Result<&Flag> {
+        self.flags.iter()
+            .find(|f| pos > 0 && f.pos == pos)
+            .ok_or(LappError(format!("no arg #{}",pos)))
+    }
+
     pub fn parse_command_line(&mut self, v: Vec<String>) -> Result<()> {
         use strutil::*;
-        let mut iter = v.into_iter();
+        let mut iter = v.into_iter().peekable();
 
         fn nextarg(name: &str, ms: Option<String>) -> Result<String> {
-            if  ms.is_none() {return error(format!("no value for flag '{}'",name));}
+            if  ms.is_none() {return lapp_error(format!("no value for flag '{}'",name));}
             Ok(ms.unwrap())
         };
 
-        // flags _may_ have the value after a = or : delimiter
-        fn extract_flag_value(s: &mut &str) -> String {
+        // flags _may_ have the value after a = or : delimiter.
+        // '--flag=' means an empty value
+        fn extract_flag_value(s: &mut &str) -> Option<String> {
             if let Some(idx) = s.find(|c: char| c == '=' || c == ':') {
                let rest = (&s[idx+1..]).to_string();
                *s = &s[0..idx];
-               rest
+               Some(rest)
             } else {
-               "".to_string()
+               None
            }
         }
 
         let mut parsing = true;
-        let mut k = 1;
         while let Some(arg) = iter.next() {
             let mut s = arg.as_str();
+            // a raw argument captures everything that follows, flags included,
+            // even when it is the first positional argument
+            let k = self.arg_pos;
+            if parsing && self.flags.iter().any(|f| f.pos == k && f.vtype == Type::Raw) {
+                parsing = false;
+            }
              if parsing && starts_with(&mut s, "--") { // long flag
                 if s.is_empty() { // plain '--' means 'stop arg processing'
                     parsing = false;
                 } else {
-                    let mut rest = extract_flag_value(&mut s);
+                    let mut value = extract_flag_value(&mut s);
+                    // '--help NAME' shows just that section
+                    if s == "help" && value.is_none()
+                        && iter.peek().is_some_and(|a| self.group_names().contains(&a.as_str())) {
+                        value = iter.next();
+                    }
+                    // '--no-cache' switches off a bool flag like '--cache (bool default true)'.
+                    // Simple flags are off anyway, so they have no '--no-' form
+                    if s.starts_with("no-") && self.flags_by_long_ref(s).is_err() {
+                        if let Ok(flag) = self.flags_by_long(&s[3..]) {
+                            if flag.vtype == Type::Bool && matches!(flag.defval, Value::Bool(true)) {
+                                flag.set_value(Value::Bool(false))?;
+                                continue;
+                            }
+                        }
+                    }
+                    if self.permissive && self.flags_by_long_ref(s).is_err() {
+                        self.unknown.push(arg.clone());
+                        continue;
+                    }
                     let flag = self.flags_by_long(s)?;
                     if flag.vtype != Type::Bool { // then it needs a value....
-                        if rest == "" {  // try grab the next arg
-                            rest = nextarg(s,iter.next())?;
+                        // unless the value is optional and the next arg is not a value
+                        if value.is_none() && flag.defval_if_present.is_some()
+                            && iter.peek().is_none_or(|a| a.starts_with('-')) {
+                            flag.set_value_if_present()?;
+                            continue;
                         }
+                        let rest = match value {
+                            Some(rest) => rest,
+                            // try grab the next arg
+                            None => nextarg(s,iter.next())?
+                        };
                         flag.set_value_from_string(&rest)?;
                     } else {
                         flag.set_value(Value::Bool(true))?;
+                        if flag.long == "help" {
+                            self.help_section = value.filter(|v| ! v.is_empty());
+                        }
                     }
                 }
             } else
@@ -371,9 +1288,18 @@ impl <'a> Args<'a> {
                 // although only the last one can take a value
                 let mut chars = s.chars();
                 while let Some(ch) = chars.next() {
+                    if self.permissive && self.flags.iter().all(|f| f.short != ch) {
+                        self.unknown.push(format!("-{}",ch));
+                        continue;
+                    }
                     let flag = self.flags_by_short(ch)?;
                     if flag.vtype != Type::Bool {
                         let mut rest: String = chars.collect();
+                        // an optional value must be attached to a short flag
+                        if rest.is_empty() && flag.defval_if_present.is_some() {
+                            flag.set_value_if_present()?;
+                            break;
+                        }
                         if rest == "" {
                             rest = nextarg(&flag.long,iter.next())?;
                         }
@@ -381,14 +1307,34 @@ impl <'a> Args<'a> {
                         break;
                     } else {
                        flag.set_value(Value::Bool(true))?;
+                       if flag.long == "help" && chars.as_str().is_empty() {
+                           let groups = self.group_names();
+                           if iter.peek().is_some_and(|a| groups.contains(&a.as_str())) {
+                               self.help_section = iter.next();
+                           }
+                           break;
+                       }
                     }
                 }
             } else {  // positional argument
-                let flag = self.flags_by_pos(k)?;
-                flag.set_value_from_string(s)?;
+                let k = self.arg_pos;
+                if self.permissive && self.flags.iter().all(|f| f.pos != k) {
+                    self.remaining.push(s.to_string());
+                    continue;
+                }
+                let (multiple, subcommand) = {
+                    let flag = self.flags_by_pos(k)?;
+                    flag.set_value_from_string(s)?;
+                    (flag.is_multiple, flag.subcommand)
+                };
+                // everything after a subcommand is left for its own spec
+                if subcommand {
+                    self.remaining.extend(iter.by_ref());
+                    break;
+                }
                 // multiple arguments are added to the vector value
-                if ! flag.is_multiple {
-                    k += 1;
+                if ! multiple {
+                    self.arg_pos += 1;
                 }
 
             }
@@ -398,25 +1344,121 @@ impl <'a> Args<'a> {
         // display usage if help is requested
         if let Ok(ref flag) = self.flags_by_long_ref("help") {
             if flag.is_set {
-                let text = strutil::dedent(self.text);
-                println!("{}",text);
+                match self.help_section {
+                    Some(ref name) => self.print_help_section(&mut io::stdout(),name),
+                    None => self.print_help(&mut io::stdout())
+                }.expect("cannot write to stdout");
                 process::exit(0);
             }
         }
 
+        self.read_passwords()?;
+
         // fill in defaults. If a default isn't available it's
         // a required flag. If not specified the flag value is set to an error
         for flag in &mut self.flags {
             flag.check()?;
         }
-        Ok(())
+        self.resolve_default_from()?;
+        self.check_exactly_one()
     }
 
-    /// clear used flag state
-    pub fn clear_used(&mut self) {
+    // password flags are asked for at the terminal. If given on the
+    // command-line they are accepted, with a warning
+    fn read_passwords(&mut self) -> Result<()> {
+        self.read_passwords_with(prompt_password)
+    }
+
+    // the prompt is passed in, so that it can be tested
+    fn read_passwords_with<F>(&mut self, prompt: F) -> Result<()>
+    where F: Fn(&str) -> Result<Option<String>> {
+        for flag in &mut self.flags {
+            if flag.vtype != Type::Password {
+                continue;
+            }
+            if flag.is_set {
+                eprintln!("warning: password '{}' given on the command-line may be seen by others",flag.long);
+            } else if let Some(pwd) = prompt(&flag.long)? {
+                flag.set_value_from_string(&pwd)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_exactly_one(&self) -> Result<()> {
+        let mut groups: Vec<_> = self.exactly_one.iter().collect();
+        groups.sort();
+        for (group, names) in groups {
+            let given = self.flags.iter().filter(|f| f.is_set && names.contains(&f.long)).count();
+            if given != 1 {
+                let names: Vec<String> = names.iter().map(|n| format!("--{}",n)).collect();
+                return lapp_error(format!("group '{}': exactly one of {} must be given",group,names.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// check the total number of positional arguments actually given, across
+    /// all the positional slots. Defaults do not count.
+    pub fn validate_arg_count_result(&self, min: usize, max: Option<usize>) -> Result<()> {
+        let count: usize = self.flags.iter()
+            .filter(|f| f.pos > 0 && f.is_set)
+            .map(|f| f.strings.len())
+            .sum();
+        if count < min {
+            return lapp_error(format!("expected at least {} arguments, got {}",min,count));
+        }
+        match max {
+            Some(max) if count > max =>
+                lapp_error(format!("expected at most {} arguments, got {}",max,count)),
+            _ => Ok(())
+        }
+    }
+
+    /// check the total number of positional arguments, quitting otherwise.
+    pub fn validate_arg_count(&self, min: usize, max: Option<usize>) {
+        self.unwrap(self.validate_arg_count_result(min,max))
+    }
+
+    /// was this flag given explicitly on the command-line?
+    /// Quits if it's an unknown flag
+    pub fn flag_given(&self, name: &str) -> bool {
+        match self.flags_by_long_ref(name) {
+            Ok(flag) => flag.is_set,
+            Err(_) => self.quit(&format!("'{}' is not a flag",name))
+        }
+    }
+
+    /// after parsing, give `to` the value of `from`, if `from` was given.
+    /// Useful when an old flag name is kept as an alias for a new one.
+    /// The flags must have the same type.
+    pub fn copy_flag_value(&mut self, from: &str, to: &str) -> Result<()> {
+        let (value, strings) = {
+            let src = self.flags_by_long_ref(from)?;
+            let dest = self.flags_by_long_ref(to)?;
+            if src.vtype != dest.vtype || src.is_multiple != dest.is_multiple {
+                return lapp_error(format!("cannot copy flag '{}' ({}) to '{}' ({})",
+                    from,src.vtype.short_name(),to,dest.vtype.short_name()));
+            }
+            if ! src.is_set {
+                return Ok(());
+            }
+            (src.value.clone(), src.strings.clone())
+        };
+        let dest = self.flags_by_long(to)?;
+        dest.value = value;
+        dest.strings = strings;
+        dest.is_set = true;
+        dest.has_value = true;
+        Ok(())
+    }
+
+    /// clear used flag state
+    pub fn clear_used(&mut self) {
         for flag in &mut self.flags {
             flag.uncheck();
         }
+        self.arg_pos = 1;
     }
 
     /// clear all the flags - ready to parse a new command line.
@@ -424,6 +1466,15 @@ impl <'a> Args<'a> {
         for flag in &mut self.flags {
             flag.clear();
         }
+        self.unknown.clear();
+        self.remaining.clear();
+        self.arg_pos = 1;
+    }
+
+    /// parse a new command line with the same spec, forgetting the old values.
+    pub fn reparse(&mut self, v: Vec<String>) -> Result<()> {
+        self.clear();
+        self.parse_command_line(v)
     }
 
     fn error_msg(&self, tname: &str, msg: &str, pos: Option<usize>) -> String {
@@ -440,13 +1491,13 @@ impl <'a> Args<'a> {
         } else {
             None
         };
-        error(&self.error_msg(tname,msg,pos))
+        lapp_error(&self.error_msg(tname,msg,pos))
     }
 
     fn unwrap<T>(&self, res: Result<T>) -> T {
         match res {
             Ok(v) => v,
-            Err(e) => self.quit(e.description())
+            Err(e) => self.quit(&e.0)
         }
     }
 
@@ -454,8 +1505,11 @@ impl <'a> Args<'a> {
     // Second, the flag's value was not set. Third, the flag's value was an error.
     fn result_flag_flag (&self, name: &str) -> Result<&Flag> {
         if let Ok(ref flag) = self.flags_by_long_ref(name) {
-           if flag.value.is_none() {
-                self.bad_flag(name,"is required")
+           if self.warn_unused {
+                flag.accessed.set(true);
+            }
+           if ! flag.has_value {
+                self.bad_flag(name,if flag.is_optional {"was not given"} else {"is required"})
             } else {
                 if let Value::Error(ref s) = flag.value {
                    self.bad_flag(name,s)
@@ -480,21 +1534,21 @@ impl <'a> Args<'a> {
             Ok(value) => {
                 match extract(value) {
                     Ok(v) => Ok(v),
-                    Err(e) => self.bad_flag(name,e.description())
+                    Err(e) => self.bad_flag(name,&e.0)
                 }
             },
             Err(e) => Err(e)
         }
     }
 
-    /// has this flag been set? Quits if it's an unknown flag
+    /// does this flag have a value, either given or from its default?
+    /// Quits if it's an unknown flag
     pub fn flag_present(&self, name: &str) -> bool {
         if let Ok(ref flag) = self.flags_by_long_ref(name) {
-           if flag.value.is_none() {
-                false
-            } else {
-                true
+            if self.warn_unused {
+                flag.accessed.set(true);
             }
+            flag.has_value
         } else {
             self.quit(&format!("'{}' is not a flag",name));
         }
@@ -511,11 +1565,44 @@ impl <'a> Args<'a> {
         self.result_flag(name,|v| v.as_int())
     }
 
-    /// get flag as a float
+    /// get flag as an unsigned 32-bit integer
+    pub fn get_u32_result(&self, name: &str) -> Result<u32> {
+        self.result_flag(name,|v| v.as_u32())
+    }
+
+    /// get flag as an unsigned 64-bit integer
+    pub fn get_u64_result(&self, name: &str) -> Result<u64> {
+        self.result_flag(name,|v| v.as_u64())
+    }
+
+    /// get flag as a size or index
+    pub fn get_usize_result(&self, name: &str) -> Result<usize> {
+        self.result_flag(name,|v| v.as_usize())
+    }
+
+    /// get flag as a 64-bit integer
+    pub fn get_i64_result(&self, name: &str) -> Result<i64> {
+        self.result_flag(name,|v| v.as_i64())
+    }
+
+    /// get flag as a 64-bit float
+    pub fn get_f64_result(&self, name: &str) -> Result<f64> {
+        self.result_flag(name,|v| v.as_f64())
+    }
+
+    /// get flag as a float. A number flag is narrowed to f32
     pub fn get_float_result(&self, name: &str) -> Result<f32> {
+        if let Ok(&Type::Number) = self.get_flag_type(name) {
+            return self.get_number_result(name).map(|x| x as f32);
+        }
         self.result_flag(name,|v| v.as_float())
     }
 
+    /// get flag as a number, which may be integer or float
+    pub fn get_number_result(&self, name: &str) -> Result<f64> {
+        self.result_flag(name,|v| v.as_number())
+    }
+
     /// get flag as boolean
     pub fn get_bool_result(&self, name: &str) -> Result<bool> {
         self.result_flag(name,|v| v.as_bool())
@@ -531,11 +1618,45 @@ impl <'a> Args<'a> {
         self.result_flag(name,|v| v.as_outfile())
     }
 
+    /// get flag as a buffered file for reading
+    pub fn get_buffered_infile_result(&self, name: &str) -> Result<io::BufReader<Box<dyn Read>>> {
+        Ok(io::BufReader::new(self.get_infile_result(name)?))
+    }
+
+    /// get flag as a buffered file for writing
+    pub fn get_buffered_outfile_result(&self, name: &str) -> Result<io::BufWriter<Box<dyn Write>>> {
+        Ok(io::BufWriter::new(self.get_outfile_result(name)?))
+    }
+
     /// get flag as a path
     pub fn get_path_result(&self, name: &str) -> Result<PathBuf> {
         self.result_flag(name,|v| v.as_path())
     }
 
+    /// get flag as a URL
+    #[cfg(feature = "url-type")]
+    pub fn get_url_result(&self, name: &str) -> Result<url::Url> {
+        let s = self.get_string_result(name)?;
+        match url::Url::parse(&s) {
+            Ok(u) => Ok(u),
+            Err(e) => self.bad_flag(name,&format!("'{}' is not a valid URL: {}",s,e))
+        }
+    }
+
+    /// get flag as a path string, with the separators normalized for this OS.
+    /// The flag may also be a plain string.
+    pub fn get_path_string_result(&self, name: &str) -> Result<String> {
+        self.result_flag(name,|v| {
+            let path = match v.as_str() {
+                Ok(s) => PathBuf::from(s),
+                Err(_) => v.as_path()?
+            };
+            // rebuilding from the components uses the native separator
+            let path: PathBuf = path.components().collect();
+            Ok(path.to_string_lossy().into_owned())
+        })
+    }
+
     /// get flag always as text, if it's defined
     pub fn get_text_result(&self, name: &str) -> Result<&String> {
         self.result_flag_flag(name).map(|f| &f.strings[0])
@@ -553,6 +1674,49 @@ impl <'a> Args<'a> {
         }
     }
 
+    /// get flag as any value which can be converted directly from a `Value`,
+    /// without going through a string like `get_result`.
+    pub fn get_typed_result<'b, T>(&'b self, name: &str) -> Result<T>
+    where T: TryFrom<&'b Value>, <T as TryFrom<&'b Value>>::Error : Display
+    {
+        match T::try_from(self.result_flag_value(name)?) {
+            Ok(v) => Ok(v),
+            Err(e) => self.bad_flag(name,&e.to_string())
+        }
+    }
+
+    /// get flag as any value which can be converted from an owned `Value`,
+    /// like a newtype or an enum, quitting otherwise.
+    pub fn get_typed<T: TryFrom<Value, Error=LappError>>(&self, name: &str) -> T {
+        let res = self.result_flag_value(name).and_then(|v| match T::try_from(v.clone()) {
+            Ok(v) => Ok(v),
+            Err(e) => self.bad_flag(name,&e.0)
+        });
+        self.unwrap(res)
+    }
+
+    /// get the value of a positional argument, counting from 1
+    pub fn get_by_pos_result(&self, n: usize) -> Result<&Value> {
+        let name = &self.flags_by_pos_ref(n)?.long;
+        self.result_flag_value(name)
+    }
+
+    /// get a positional argument as a string, counting from 1
+    pub fn get_string_by_pos_result(&self, n: usize) -> Result<String> {
+        let name = &self.flags_by_pos_ref(n)?.long;
+        self.get_string_result(name)
+    }
+
+    /// get the value of a positional argument, quitting otherwise.
+    pub fn get_by_pos(&self, n: usize) -> &Value {
+        self.unwrap(self.get_by_pos_result(n))
+    }
+
+    /// get a positional argument as a string, quitting otherwise.
+    pub fn get_string_by_pos(&self, n: usize) -> String {
+        self.unwrap(self.get_string_by_pos_result(n))
+    }
+
     /// get flag as a string, quitting otherwise.
     pub fn get_string(&self, name: &str) -> String {
         self.unwrap(self.get_string_result(name))
@@ -563,11 +1727,41 @@ impl <'a> Args<'a> {
         self.unwrap(self.get_integer_result(name))
     }
 
+    /// get flag as an unsigned 32-bit integer, quitting otherwise.
+    pub fn get_u32(&self, name: &str) -> u32 {
+        self.unwrap(self.get_u32_result(name))
+    }
+
+    /// get flag as an unsigned 64-bit integer, quitting otherwise.
+    pub fn get_u64(&self, name: &str) -> u64 {
+        self.unwrap(self.get_u64_result(name))
+    }
+
+    /// get flag as a size or index, quitting otherwise.
+    pub fn get_usize(&self, name: &str) -> usize {
+        self.unwrap(self.get_usize_result(name))
+    }
+
+    /// get flag as a 64-bit integer, quitting otherwise.
+    pub fn get_i64(&self, name: &str) -> i64 {
+        self.unwrap(self.get_i64_result(name))
+    }
+
+    /// get flag as a 64-bit float, quitting otherwise.
+    pub fn get_f64(&self, name: &str) -> f64 {
+        self.unwrap(self.get_f64_result(name))
+    }
+
     /// get flag as a float, quitting otherwise.
     pub fn get_float(&self, name: &str) -> f32 {
         self.unwrap(self.get_float_result(name))
     }
 
+    /// get flag as a number, quitting otherwise.
+    pub fn get_number(&self, name: &str) -> f64 {
+        self.unwrap(self.get_number_result(name))
+    }
+
     /// get flag as a bool, quitting otherwise.
     pub fn get_bool(&self, name: &str) -> bool {
         self.unwrap(self.get_bool_result(name))
@@ -583,11 +1777,44 @@ impl <'a> Args<'a> {
         self.unwrap(self.get_outfile_result(name))
     }
 
+    /// get flag as a buffered file for reading, quitting otherwise.
+    pub fn get_buffered_infile(&self, name: &str) -> io::BufReader<Box<dyn Read>> {
+        self.unwrap(self.get_buffered_infile_result(name))
+    }
+
+    /// get flag as a buffered file for writing, quitting otherwise.
+    pub fn get_buffered_outfile(&self, name: &str) -> io::BufWriter<Box<dyn Write>> {
+        self.unwrap(self.get_buffered_outfile_result(name))
+    }
+
     /// get flag as a path, quitting otherwise.
     pub fn get_path(&self, name: &str) -> PathBuf {
         self.unwrap(self.get_path_result(name))
     }
 
+    /// get flag as a compiled regular expression
+    #[cfg(feature = "regex")]
+    pub fn get_regex_result(&self, name: &str) -> Result<regex::Regex> {
+        self.result_flag(name,|v| v.as_regex())
+    }
+
+    /// get flag as a URL, quitting otherwise.
+    #[cfg(feature = "url-type")]
+    pub fn get_url(&self, name: &str) -> url::Url {
+        self.unwrap(self.get_url_result(name))
+    }
+
+    /// get flag as a compiled regular expression, quitting otherwise.
+    #[cfg(feature = "regex")]
+    pub fn get_regex(&self, name: &str) -> regex::Regex {
+        self.unwrap(self.get_regex_result(name))
+    }
+
+    /// get flag as a path string, quitting otherwise.
+    pub fn get_path_string(&self, name: &str) -> String {
+        self.unwrap(self.get_path_string_result(name))
+    }
+
     /// get flag as any value which can parsed from a string, quitting otherwise.
     pub fn get<T>(&self, name: &str) -> T
     where T: FromStr, <T as FromStr>::Err : Display
@@ -598,19 +1825,101 @@ impl <'a> Args<'a> {
         }
     }
 
+    /// get flag as any value which can parsed from a string, calling `default_fn`
+    /// only if the flag has no value. Quits if the value is bad.
+    pub fn get_or_else<T, F: Fn() -> T>(&self, name: &str, default_fn: F) -> T
+    where T: FromStr, <T as FromStr>::Err : Display
+    {
+        if self.flag_present(name) {
+            self.get(name)
+        } else {
+            default_fn()
+        }
+    }
+
+    /// get flag as a string, calling `default_fn` if it has no value.
+    pub fn get_string_or_else<F: Fn() -> String>(&self, name: &str, default_fn: F) -> String {
+        if self.flag_present(name) { self.get_string(name) } else { default_fn() }
+    }
+
+    /// get flag as an integer, calling `default_fn` if it has no value.
+    pub fn get_integer_or_else<F: Fn() -> i32>(&self, name: &str, default_fn: F) -> i32 {
+        if self.flag_present(name) { self.get_integer(name) } else { default_fn() }
+    }
+
+    /// get flag as a float, calling `default_fn` if it has no value.
+    pub fn get_float_or_else<F: Fn() -> f32>(&self, name: &str, default_fn: F) -> f32 {
+        if self.flag_present(name) { self.get_float(name) } else { default_fn() }
+    }
+
+    /// get flag as any value which can parsed from a string. If the flag has no
+    /// value and we are reading from a terminal, ask for it, trying up to three times.
+    /// Otherwise quits, like `get`.
+    pub fn get_or_prompt<T>(&self, name: &str, prompt: &str) -> T
+    where T: FromStr, <T as FromStr>::Err : Display
+    {
+        if self.flag_present(name) || ! io::stdin().is_terminal() {
+            return self.get(name);
+        }
+        let stdin = io::stdin();
+        let res = self.prompt_for(name,prompt,&mut stdin.lock(),&mut io::stderr());
+        self.unwrap(res)
+    }
+
+    fn prompt_for<T, R: BufRead, W: Write>(&self, name: &str, prompt: &str, input: &mut R, out: &mut W) -> Result<T>
+    where T: FromStr, <T as FromStr>::Err : Display
+    {
+        let mut last_error = String::new();
+        for _ in 0..3 {
+            let _ = write!(out,"{}",prompt);
+            let _ = out.flush();
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            match line.trim_end_matches(['\r','\n']).parse::<T>() {
+                Ok(v) => return Ok(v),
+                Err(e) => {
+                    last_error = e.to_string();
+                    let _ = writeln!(out,"{}",last_error);
+                }
+            }
+        }
+        if last_error.is_empty() {
+            self.bad_flag(name,"is required")
+        } else {
+            self.bad_flag(name,&last_error)
+        }
+    }
+
     fn get_boxed_array(&self, name: &str, kind: &str) -> Result<&Vec<Box<Value>>> {
+        static EMPTY: Vec<Box<Value>> = Vec::new();
+        // a multiple flag which was never given has no values, which is fine
+        if let Ok(flag) = self.flags_by_long_ref(name) {
+            if flag.is_multiple && flag.value.is_none() {
+                return Ok(&EMPTY);
+            }
+        }
         let arr = self.result_flag_value(name)?.as_array()?;
         // empty array matches all types
         if arr.len() == 0 { return Ok(arr); }
         // otherwise check the type of the first element
         let ref v = *(arr[0]);
         let tname = v.type_of().short_name();
-        if tname == kind {
-            Ok(arr)
-        } else {
+        if tname != kind {
             let msg = format!("wanted array of {}, but is array of {}",kind,tname);
-            error(self.error_msg(name,&msg,None))
+            return lapp_error(self.error_msg(name,&msg,None));
         }
+        // and that the rest are the same
+        for (i, v) in arr.iter().enumerate().skip(1) {
+            let tname = v.type_of().short_name();
+            if tname != kind {
+                let msg = format!("wanted array of {}, but element {} is {}",kind,i+1,tname);
+                return lapp_error(self.error_msg(name,&msg,None));
+            }
+        }
+        Ok(arr)
     }
 
     fn get_array_result<T,F>(&self, name: &str, kind: &str, extract: F) -> Result<Vec<T>>
@@ -624,6 +1933,81 @@ impl <'a> Args<'a> {
         Ok(res)
     }
 
+    /// get a multiple or array flag as a vector of any type which can be
+    /// converted directly from a `Value` (see `get_typed_result`)
+    pub fn get_typed_array_result<'b, T>(&'b self, name: &str) -> Result<Vec<T>>
+    where T: TryFrom<&'b Value>, <T as TryFrom<&'b Value>>::Error : Display
+    {
+        let mut res = Vec::new();
+        for v in self.result_flag_value(name)?.as_array()? {
+            if let Value::Error(ref e) = **v {
+                return self.bad_flag(name,e);
+            }
+            match T::try_from(v) {
+                Ok(v) => res.push(v),
+                Err(e) => return self.bad_flag(name,&e.to_string())
+            }
+        }
+        Ok(res)
+    }
+
+    /// get a multiple or array flag as a vector of any type which can be
+    /// converted from a `Value`, quitting otherwise.
+    pub fn get_typed_array<'b, T>(&'b self, name: &str) -> Vec<T>
+    where T: TryFrom<&'b Value>, <T as TryFrom<&'b Value>>::Error : Display
+    {
+        self.unwrap(self.get_typed_array_result(name))
+    }
+
+    fn get_pairs_result(&self, name: &str) -> Result<Vec<(&str,&str)>> {
+        if *self.get_flag_type(name)? != Type::KeyVal {
+            return self.bad_flag(name,"not a keyval flag");
+        }
+        for v in self.result_flag_value(name)?.as_array()? {
+            if let Value::Error(ref e) = **v {
+                return self.bad_flag(name,e);
+            }
+        }
+        let mut res = Vec::new();
+        for v in self.get_boxed_array(name,"string")? {
+            let s = v.as_str()?;
+            let idx = s.find('=').unwrap();
+            res.push((&s[..idx], &s[idx+1..]));
+        }
+        Ok(res)
+    }
+
+    /// get a keyval flag as a map, converting each value with a closure.
+    /// Values are passed as integers or floats if they look like numbers.
+    pub fn get_map_result<V,F>(&self, name: &str, extract: F) -> Result<HashMap<String,V>>
+    where F: Fn(&Value) -> Result<V> {
+        let mut res = HashMap::new();
+        for (key, val) in self.get_pairs_result(name)? {
+            let v = match Type::Int.parse_string(val)? {
+                Value::Int(n) => Value::Int(n),
+                _ => match val.chars().next() {
+                    Some(c) if c.is_ascii_digit() || c == '-' || c == '.' => match Type::Float.parse_string(val)? {
+                        Value::Float(x) => Value::Float(x),
+                        _ => Value::Str(val.into())
+                    },
+                    _ => Value::Str(val.into())
+                }
+            };
+            match extract(&v) {
+                Ok(v) => { res.insert(key.to_string(),v); },
+                Err(e) => return self.bad_flag(name,&format!("key '{}': {}",key,e))
+            }
+        }
+        Ok(res)
+    }
+
+    /// get a keyval flag as a map of strings
+    pub fn get_string_map_result(&self, name: &str) -> Result<HashMap<String,String>> {
+        Ok(self.get_pairs_result(name)?.into_iter()
+            .map(|(k,v)| (k.to_string(),v.to_string()))
+            .collect())
+    }
+
     /// get a multiple flag as an array of strings
     pub fn get_strings_result(&self, name: &str) -> Result<Vec<String>> {
         self.get_array_result(name,"string",|b| b.as_string())
@@ -634,11 +2018,81 @@ impl <'a> Args<'a> {
         self.get_array_result(name,"integer",|b| b.as_int())
     }
 
+    /// get a multiple flag as an array of u32
+    pub fn get_u32s_result(&self, name: &str) -> Result<Vec<u32>> {
+        self.get_array_result(name,"u32",|b| b.as_u32())
+    }
+
+    /// get a multiple flag as an array of u64
+    pub fn get_u64s_result(&self, name: &str) -> Result<Vec<u64>> {
+        self.get_array_result(name,"u64",|b| b.as_u64())
+    }
+
+    /// get a multiple flag as an array of usize
+    pub fn get_usizes_result(&self, name: &str) -> Result<Vec<usize>> {
+        self.get_array_result(name,"usize",|b| b.as_usize())
+    }
+
+    /// get a multiple flag as an array of i64
+    pub fn get_i64s_result(&self, name: &str) -> Result<Vec<i64>> {
+        self.get_array_result(name,"i64",|b| b.as_i64())
+    }
+
+    /// get a multiple flag as an array of f64
+    pub fn get_f64s_result(&self, name: &str) -> Result<Vec<f64>> {
+        self.get_array_result(name,"f64",|b| b.as_f64())
+    }
+
+    /// get a multiple flag as an array of paths
+    pub fn get_paths_result(&self, name: &str) -> Result<Vec<PathBuf>> {
+        self.get_array_result(name,"path",|b| b.as_path())
+    }
+
     /// get a multiple flag as an array of floats
     pub fn get_floats_result(&self, name: &str) -> Result<Vec<f32>> {
         self.get_array_result(name,"float",|b| b.as_float())
     }
 
+    /// get a multiple number flag as an array
+    pub fn get_numbers_result(&self, name: &str) -> Result<Vec<f64>> {
+        // number flags hold f64 values, but float arrays are widened too
+        let kind = match self.flags_by_long_ref(name).map(|f| f.effective_type()) {
+            Ok(&Type::Float) => "float",
+            _ => "f64"
+        };
+        self.get_array_result(name,kind,|b| b.as_number())
+    }
+
+    /// get a multiple integer flag as an array of floats.
+    /// An f32 only holds integers up to 2^24 (16777216) exactly; larger values are rounded.
+    pub fn get_integers_as_floats_result(&self, name: &str) -> Result<Vec<f32>> {
+        self.get_array_result(name,"integer",|b| b.as_int().map(|n| n as f32))
+    }
+
+    /// get an integer flag as a float. Integers beyond 2^24 in size are rounded,
+    /// so use `get_coerced::<f64>` when they must be exact.
+    pub fn get_integer_as_float_result(&self, name: &str) -> Result<f32> {
+        self.result_flag(name,|v| v.as_int().map(|n| n as f32))
+    }
+
+    /// get flag as a float, where an integer flag is also fine
+    pub fn get_float_loose_result(&self, name: &str) -> Result<f32> {
+        self.result_flag(name,|v| match *v {
+            Value::Int(n) => Ok(n as f32),
+            _ => v.as_float()
+        })
+    }
+
+    /// get a number flag as any type which both integers and floats convert into, like f64
+    pub fn get_coerced_result<T>(&self, name: &str) -> Result<T>
+    where T: From<i32> + From<f32>
+    {
+        self.result_flag(name,|v| match *v {
+            Value::Int(n) => Ok(T::from(n)),
+            _ => v.as_float().map(T::from)
+        })
+    }
+
     /// get a multiple flag as an array of any parsable value.
     pub fn get_results<T>(&self, name: &str) -> Result<Vec<T>>
     where T: FromStr, <T as FromStr>::Err : Display
@@ -654,6 +2108,44 @@ impl <'a> Args<'a> {
     }
 
 
+    // lazily extract the values of a multiple flag, quitting on the first bad value
+    fn iter_extract<'b,T,F>(&'b self, name: &str, kind: &str, extract: F) -> impl Iterator<Item=T> + 'b
+    where T: 'b, F: Fn(&'b Value) -> Result<T> + 'b {
+        let arr = self.unwrap(self.get_boxed_array(name,kind));
+        let name = name.to_string();
+        arr.iter().map(move |b| match extract(b) {
+            Ok(v) => v,
+            Err(e) => self.quit(&self.error_msg(&name,&e.0,None))
+        })
+    }
+
+    /// iterate over the values of a multiple flag without copying them,
+    /// quitting if the flag is not an array.
+    pub fn iter_multiple<'b>(&'b self, name: &str) -> impl Iterator<Item=&'b Value> + 'b {
+        let arr = self.unwrap(self.result_flag_value(name).and_then(|v| v.as_array()));
+        arr.iter().map(|b| &**b)
+    }
+
+    /// iterate over a multiple flag as strings, quitting otherwise
+    pub fn iter_multiple_strings<'b>(&'b self, name: &str) -> impl Iterator<Item=&'b str> + 'b {
+        self.iter_extract(name,"string",|v| v.as_str())
+    }
+
+    /// iterate over a multiple flag as integers, quitting otherwise
+    pub fn iter_multiple_integers<'b>(&'b self, name: &str) -> impl Iterator<Item=i32> + 'b {
+        self.iter_extract(name,"integer",|v| v.as_int())
+    }
+
+    /// iterate over a multiple flag as floats, quitting otherwise
+    pub fn iter_multiple_floats<'b>(&'b self, name: &str) -> impl Iterator<Item=f32> + 'b {
+        self.iter_extract(name,"float",|v| v.as_float())
+    }
+
+    /// get a keyval flag as a map of strings, quitting otherwise
+    pub fn get_string_map(&self, name: &str) -> HashMap<String,String> {
+        self.unwrap(self.get_string_map_result(name))
+    }
+
     /// get a multiple flag as an array of strings, quitting otherwise
     pub fn get_strings(&self, name: &str) -> Vec<String> {
         self.unwrap(self.get_strings_result(name))
@@ -664,11 +2156,69 @@ impl <'a> Args<'a> {
         self.unwrap(self.get_integers_result(name))
     }
 
+    /// get a multiple flag as an array of u32, quitting otherwise
+    pub fn get_u32s(&self, name: &str) -> Vec<u32> {
+        self.unwrap(self.get_u32s_result(name))
+    }
+
+    /// get a multiple flag as an array of u64, quitting otherwise
+    pub fn get_u64s(&self, name: &str) -> Vec<u64> {
+        self.unwrap(self.get_u64s_result(name))
+    }
+
+    /// get a multiple flag as an array of usize, quitting otherwise
+    pub fn get_usizes(&self, name: &str) -> Vec<usize> {
+        self.unwrap(self.get_usizes_result(name))
+    }
+
+    /// get a multiple flag as an array of i64, quitting otherwise
+    pub fn get_i64s(&self, name: &str) -> Vec<i64> {
+        self.unwrap(self.get_i64s_result(name))
+    }
+
+    /// get a multiple flag as an array of f64, quitting otherwise
+    pub fn get_f64s(&self, name: &str) -> Vec<f64> {
+        self.unwrap(self.get_f64s_result(name))
+    }
+
+    /// get a multiple flag as an array of paths, quitting otherwise
+    pub fn get_paths(&self, name: &str) -> Vec<PathBuf> {
+        self.unwrap(self.get_paths_result(name))
+    }
+
     /// get a multiple flag as an array of floats, quitting otherwise
     pub fn get_floats(&self, name: &str) -> Vec<f32> {
         self.unwrap(self.get_floats_result(name))
     }
 
+    /// get a multiple number flag as an array, quitting otherwise
+    pub fn get_numbers(&self, name: &str) -> Vec<f64> {
+        self.unwrap(self.get_numbers_result(name))
+    }
+
+    /// get a multiple integer flag as an array of floats, quitting otherwise
+    pub fn get_integers_as_floats(&self, name: &str) -> Vec<f32> {
+        self.unwrap(self.get_integers_as_floats_result(name))
+    }
+
+    /// get an integer flag as a float, quitting otherwise
+    pub fn get_integer_as_float(&self, name: &str) -> f32 {
+        self.unwrap(self.get_integer_as_float_result(name))
+    }
+
+    /// get flag as a float, where an integer flag is also fine, quitting otherwise
+    pub fn get_float_loose(&self, name: &str) -> f32 {
+        self.unwrap(self.get_float_loose_result(name))
+    }
+
+    /// get a number flag as any type which both integers and floats convert into,
+    /// quitting otherwise
+    pub fn get_coerced<T>(&self, name: &str) -> T
+    where T: From<i32> + From<f32>
+    {
+        self.unwrap(self.get_coerced_result(name))
+    }
+
     /// get a multiple flag as an array of any parsable value, quitting otherwise
     pub fn get_array<T>(&self, name: &str) -> Vec<T>
     where T: FromStr, <T as FromStr>::Err : Display
@@ -679,13 +2229,82 @@ impl <'a> Args<'a> {
 
 }
 
+impl <'a> Drop for Args<'a> {
+    fn drop(&mut self) {
+        if self.warn_unused {
+            self.report_unused();
+        }
+    }
+}
+
+// ask for a password without echo, if we are on a terminal
+#[cfg(feature = "password-prompt")]
+fn prompt_password(name: &str) -> Result<Option<String>> {
+    if ! io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    match rpassword::prompt_password(format!("{}: ",name)) {
+        Ok(pwd) => Ok(Some(pwd)),
+        Err(e) => lapp_error(format!("cannot read password '{}': {}",name,e))
+    }
+}
+
+// without the prompt, a password is just a required string
+#[cfg(not(feature = "password-prompt"))]
+fn prompt_password(_name: &str) -> Result<Option<String>> {
+    Ok(None)
+}
+
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+// replace any '@include "file"' lines with the contents of that file,
+// relative to `base`. Returns None if there are no includes.
+fn expand_includes(text: &str, base: &Path, depth: usize) -> Result<Option<String>> {
+    use strutil::*;
+    if ! text.lines().any(|line| skipws(line).starts_with("@include")) {
+        return Ok(None);
+    }
+    if depth > MAX_INCLUDE_DEPTH {
+        return lapp_error("@include nested too deeply");
+    }
+    let mut res = String::new();
+    for line in text.lines() {
+        let mut slice = skipws(line);
+        if starts_with(&mut slice,"@include") {
+            slice = skipws(slice);
+            let quote = if starts_with(&mut slice,"\"") {
+                "\""
+            } else if starts_with(&mut slice,"'") {
+                "'"
+            } else {
+                return lapp_error(format!("@include needs a quoted file name\nat line: '{}'",line));
+            };
+            let path = base.join(grab_upto(&mut slice,quote)?);
+            let contents = match fs::read_to_string(&path) {
+                Ok(s) => s,
+                Err(e) => return lapp_error(format!("cannot read included spec '{}': {}",path.display(),e))
+            };
+            let dir = path.parent().unwrap_or(base);
+            match expand_includes(&contents,dir,depth+1)? {
+                Some(expanded) => res += &expanded,
+                None => res += &contents
+            }
+            if ! res.ends_with('\n') {
+                res.push('\n');
+            }
+        } else {
+            res += line;
+            res.push('\n');
+        }
+    }
+    Ok(Some(res))
+}
+
 /// parse the command-line specification and use it
 /// to parse the program's command line args.
 /// As before, quits on any error.
 pub fn parse_args(s: &str) -> Args {
-    let mut res = Args::new(s);
-    res.parse();
-    res
+    Args::new(s).parse_owned()
 }
 
 #[cfg(test)]
@@ -715,7 +2334,7 @@ mod tests {
     fn parse_args(spec: &'static str, parms: &[&str]) -> Args<'static> {
         let mut args = Args::new(spec);
         args.parse_spec().expect("spec failed");
-        args.parse_command_line(arg_strings(parms)).expect("scan failed");
+        args.try_parse_from(parms.iter().cloned()).expect("scan failed");
         args
     }
 
@@ -796,7 +2415,7 @@ mod tests {
     }
 
     fn err<T>(r: Result<T>) -> String {
-        r.err().unwrap().description().to_string()
+        r.err().unwrap().to_string()
     }
 
     fn ok<T>(r: Result<T>) -> T {
@@ -850,10 +2469,1666 @@ mod tests {
         let mut args = Args::new(CUSTOM);
         args.user_types(&["hex"]);
         args.parse_spec().expect("spec failed");
-        args.parse_command_line(arg_strings(&["--hex","FF"])).expect("scan failed");
+        args.try_parse_from(["--hex","FF"]).expect("scan failed");
         let hex: Hex = args.get("hex");
         assert_eq!(hex.value,0xFF);
     }
 
+    #[test]
+    fn test_tokenize() {
+        use strutil::tokenize;
+        assert_eq!(ok(tokenize("one  two\tthree")),["one","two","three"]);
+        assert_eq!(ok(tokenize(r#"'a b' "c \"d\"" e\ f"#)),["a b","c \"d\"","e f"]);
+        assert_eq!(ok(tokenize(r#"'no \escape' "\n""#)),["no \\escape","\\n"]);
+        assert_eq!(ok(tokenize("x # a comment\ny")),["x","y"]);
+        assert_eq!(ok(tokenize("x#y ''")),["x#y",""]);
+        assert_eq!(err(tokenize("'open")),"unclosed quote '\\''");
+        assert_eq!(err(tokenize("\"open")),"unclosed quote '\"'");
+    }
+
+    #[test]
+    fn test_include() {
+        use std::fs;
+        let dir = ::std::env::temp_dir().join(format!("lapp-include-{}",::std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("common.lapp"),"  -v, --verbose\n  @include \"sub/io.lapp\"\n").unwrap();
+        fs::write(dir.join("sub/io.lapp"),"  -o, --output (default 'stdout')\n").unwrap();
+        fs::write(dir.join("loop.lapp"),"@include 'loop.lapp'\n").unwrap();
+
+        let mut args = Args::new("
+            Including other specs
+            @include \"common.lapp\"
+            <file> (string)
+        ");
+        args.parse_spec_with_context(&dir).expect("spec failed");
+        args.try_parse_from(["-v","x.txt"]).expect("scan failed");
+        assert_eq!(args.get_bool("verbose"),true);
+        assert_eq!(args.get_string("output"),"stdout");
+        assert_eq!(args.get_string("file"),"x.txt");
+        assert!(! args.text.contains("@include"));
+        assert!(args.text.contains("--output"));
+
+        let mut args = Args::new("@include 'loop.lapp'");
+        assert_eq!(err(args.parse_spec_with_context(&dir)),"@include nested too deeply");
+        let mut args = Args::new("@include 'missing.lapp'");
+        assert!(err(args.parse_spec_with_context(&dir)).starts_with("cannot read included spec"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_short() {
+        let mut args = Args::new("
+            -v, --verbose
+            -v, --version
+        ");
+        assert!(err(args.parse_spec()).starts_with("short flag 'v' already defined: flag 'version'"));
+
+        // help gives way to a user's -h
+        let aa = parse_args("-h, --host (string)",&["-h","localhost"]);
+        assert_eq!(aa.get_string("host"),"localhost");
+        assert!(! aa.get_bool("help"));
+    }
+
+    #[cfg(feature = "build")]
+    #[test]
+    fn test_validate_spec_file() {
+        use std::fs;
+        let dir = ::std::env::temp_dir().join(format!("lapp-build-{}",::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("good.lapp"),"  -v, --verbose\n  <file> (string)\n").unwrap();
+        fs::write(dir.join("bad.lapp"),"  -v, --verbose\n  --verbose (string)\n").unwrap();
+        assert!(build::validate_spec_file(&dir.join("good.lapp")).is_ok());
+        let msg = err(build::validate_spec_file(&dir.join("bad.lapp")));
+        assert!(msg.contains("bad.lapp: already defined: flag 'verbose'"));
+        assert!(err(build::validate_spec_file(&dir.join("none.lapp"))).starts_with("cannot read spec"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hex() {
+        let spec = "
+            --reg (integer hex)
+            --mask (integer hex default FF)
+        ";
+        let aa = parse_args(spec,&["--reg","DEAD"]);
+        assert_eq!(aa.get_integer("reg"),0xDEAD);
+        assert_eq!(aa.get_integer("mask"),255);
+        let aa = parse_args(spec,&["--reg","0xbeef"]);
+        assert_eq!(aa.get_integer("reg"),0xBEEF);
+        let aa = parse_args(spec,&["--reg","xyz"]);
+        assert_eq!(err(aa.get_integer_result("reg")),
+            "flag 'reg': can't convert 'xyz' to hex integer - invalid digit found in string");
+
+        let mut args = Args::new(spec);
+        assert!(args.declarations("Args").contains("\treg: i32, /* hex */\n"));
+    }
+
+    #[test]
+    fn test_program_name() {
+        let args = Args::new(SIMPLE).with_program_name("mytool");
+        assert_eq!(args.quit_message("no value for flag 'output'"),
+            "mytool error: no value for flag 'output'\nType mytool --help for more information");
+    }
+
+    #[test]
+    fn test_path_string() {
+        let spec = "
+            --dir (path)
+            --name (string)
+            --count (integer default 1)
+        ";
+        let aa = parse_args(spec,&["--dir","a//b/./c/","--name","x/y"]);
+        assert_eq!(aa.get_path_string("dir"),Path::new("a").join("b").join("c").to_string_lossy());
+        assert_eq!(aa.get_path_string("name"),Path::new("x").join("y").to_string_lossy());
+        assert_eq!(err(aa.get_path_string_result("count")),"flag 'count': not a path, but integer");
+    }
+
+    #[test]
+    fn test_empty_array_type() {
+        assert_eq!(Value::empty_array().type_of(), Type::Arr(Box::new(Type::None)));
+        assert_eq!(Value::empty_array().type_of().short_name(), "array of unknown");
+        assert_eq!(err(Value::empty_array().as_int()), "not a integer, but array of unknown");
+        let aa = parse_args(SIMPLE,&["in"]);
+        assert_eq!(aa.get_integers("p"), Vec::<i32>::new());
+        assert_eq!(err(aa.get_integer_result("p")), "flag 'p': not a integer, but array of unknown");
+        let types: Vec<&Type> = aa.flags.iter().map(|f| f.effective_type()).collect();
+        assert_eq!(types,[&Type::Bool,&Type::Bool,&Type::Str,&Type::Int,&Type::Str,&Type::Str,&Type::Str,&Type::Bool]);
+    }
+
+    #[test]
+    fn test_mixed_array() {
+        let mixed = Value::Arr(vec![Box::new(Value::Int(1)),Box::new(Value::Str("x".into()))]);
+        assert_eq!(mixed.type_of(),Type::Error);
+        let same = Value::Arr(vec![Box::new(Value::Int(1)),Box::new(Value::Int(2))]);
+        assert_eq!(same.type_of(),Type::Arr(Box::new(Type::Int)));
+
+        let mut aa = parse_args("--ns... (integer)",&["--ns","1","--ns","2"]);
+        aa.flags_by_long("ns").unwrap().value = mixed;
+        assert_eq!(err(aa.get_integers_result("ns")),"flag 'ns': wanted array of integer, but element 2 is string");
+    }
+
+    #[test]
+    fn test_multiple_without_value() {
+        let mut aa = parse_args("-I, --include... (string)\n--n (integer optional)\n",&[]);
+        aa.flags_by_long("include").unwrap().value = Value::None;
+        aa.flags_by_long("include").unwrap().has_value = false;
+        assert!(aa.get_strings("include").is_empty());
+        aa.flags_by_long("n").unwrap().value = Value::None;
+        assert_eq!(err(aa.get_integers_result("n")),"flag 'n': was not given");
+    }
+
+    #[test]
+    fn test_integers_as_floats() {
+        let spec = "
+            --count (integer)
+            --scale (default 0.5)
+            --name (string)
+        ";
+        let aa = parse_args(spec,&["--count","3","--name","x"]);
+        assert_eq!(aa.get_integer_as_float("count"), 3.0);
+        assert_eq!(aa.get_coerced::<f64>("count"), 3.0);
+        assert_eq!(aa.get_coerced::<f64>("scale"), 0.5);
+        assert_eq!(err(aa.get_integer_as_float_result("scale")), "flag 'scale': not a integer, but float");
+        assert_eq!(err(aa.get_coerced_result::<f64>("name")), "flag 'name': not a float, but string");
+
+        let aa = parse_args(SIMPLE,&["-p","1 2","in"]);
+        assert_eq!(aa.get_integers_as_floats("p"), [1.0,2.0]);
+
+        // f32 is exact only up to 2^24; f64 holds any i32
+        let aa = parse_args(spec,&["--count","16777217"]);
+        assert_eq!(aa.get_integer_as_float("count"), 16777216.0);
+        assert_eq!(aa.get_coerced::<f64>("count"), 16777217.0);
+        let aa = parse_args(SIMPLE,&["-p","16777216 16777217","in"]);
+        assert_eq!(aa.get_integers_as_floats("p"), [16777216.0,16777216.0]);
+    }
+
+    #[test]
+    fn test_float_loose() {
+        let aa = parse_args("--count (integer)\n--scale (float)\n--name (string)",&["--count","3","--scale","1.5","--name","x"]);
+        assert_eq!(aa.get_float_loose("count"), 3.0);
+        assert_eq!(aa.get_float_loose("scale"), 1.5);
+        assert_eq!(err(aa.get_float_result("count")), "flag 'count': not a float, but integer");
+        assert_eq!(err(aa.get_float_loose_result("name")), "flag 'name': not a float, but string");
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn test_glob() {
+        use std::fs;
+        let dir = ::std::env::temp_dir().join(format!("lapp-glob-{}",::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for f in &["a.rs","b.rs","c.txt"] {
+            fs::write(dir.join(f),"").unwrap();
+        }
+        let rs = dir.join("*.rs").to_string_lossy().into_owned();
+        let md = dir.join("*.md").to_string_lossy().into_owned();
+        let spec = "
+            --files (string glob)
+            --docs (string glob-required)
+        ";
+        let mut args = Args::new(spec);
+        args.parse_spec().expect("spec failed");
+        args.try_parse_from(vec!["--files".into(),rs.clone(),"--files".into(),md.clone()]).expect("scan failed");
+        let files: Vec<String> = args.get_strings("files").into_iter()
+            .map(|f| Path::new(&f).file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files, ["a.rs","b.rs"]);
+        assert_eq!(args.get_strings("docs"), empty_strings());
+
+        let mut args = Args::new(spec);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(vec!["--docs".into(),md.clone()])),
+            format!("flag 'docs': no files match '{}'",md));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_present_and_given() {
+        let aa = parse_args(SIMPLE,&["-v","in"]);
+        assert!(aa.flag_present("verbose") && aa.flag_given("verbose"));
+        assert!(aa.flag_present("output") && ! aa.flag_given("output"));
+        assert!(aa.flag_present("include") && ! aa.flag_given("include"));
+        assert!(aa.flag_present("in") && aa.flag_given("in"));
+
+        let aa = parse_args("--name (string)",&[]);
+        assert!(! aa.flag_present("name"));
+        assert_eq!(err(aa.get_string_result("name")),"flag 'name': is required");
+    }
+
+    #[test]
+    fn test_spec_writer() {
+        use std::fmt::Write;
+        let mut spec = SpecWriter::new();
+        writeln!(spec, "  -v, --verbose verbose flag").unwrap();
+        write!(spec, "  --count ").unwrap();
+        writeln!(spec, "({}..{})", 1, 100).unwrap();
+        assert!(spec.error().is_none());
+        let mut args = spec.into_args();
+        args.parse_spec().expect("spec failed");
+        args.try_parse_from(["-v","--count","42"]).expect("scan failed");
+        assert!(args.get_bool("verbose"));
+        assert_eq!(args.get_integer("count"),42);
+
+        let mut spec = SpecWriter::new();
+        writeln!(spec, "  --count (integer)").unwrap();
+        assert!(writeln!(spec, "  --count (string)").is_err());
+        assert_eq!(spec.error().unwrap().0, "already defined: flag 'count'\nat line: '  --count (string)'");
+    }
+
+    #[test]
+    fn test_base() {
+        let spec = "
+            --mode (integer base 8 default 644)
+            --bits (integer base 2)
+            --reg (integer base 16)
+        ";
+        let aa = parse_args(spec,&["--bits","0b101","--reg","ff"]);
+        assert_eq!(aa.get_integer("mode"),0o644);
+        assert_eq!(aa.get_integer("bits"),5);
+        assert_eq!(aa.get_integer("reg"),255);
+        let aa = parse_args(spec,&["--mode","0o755","--bits","12","--reg","0"]);
+        assert_eq!(aa.get_integer("mode"),0o755);
+        assert_eq!(err(aa.get_integer_result("bits")),
+            "flag 'bits': can't convert '12' to base 2 integer - invalid digit found in string");
+
+        let mut args = Args::new("--n (integer base 40)");
+        assert!(err(args.parse_spec()).starts_with("'base' must be between 2 and 36"));
+    }
+
+    #[test]
+    fn test_reparse() {
+        let mut args = parse_args(SIMPLE,&["-v","-I.","-p","1 2","in","a","b"]);
+        args.reparse(arg_strings(&["-k","-I..","other","c"])).expect("reparse failed");
+        assert!(! args.get_bool("verbose"));
+        assert!(args.get_bool("k"));
+        assert_eq!(args.get_strings("include"),[".."]);
+        assert_eq!(args.get_integers("p"),Vec::<i32>::new());
+        assert_eq!(args.get_string("in"),"other");
+        assert_eq!(args.get_strings("out"),["c"]);
+        args.reparse(empty_strings()).expect("reparse failed");
+        assert_eq!(err(args.get_string_result("in")),"argument #1 'in': is required");
+    }
+
+    #[test]
+    fn test_default_if_present() {
+        let spec = "
+            -O, --optimize (integer default-if-present 1 default 0)
+            --log (string default-if-present 'app.log')
+            -v, --verbose
+        ";
+        let aa = parse_args(spec,&[]);
+        assert_eq!(aa.get_integer("optimize"),0);
+        assert_eq!(err(aa.get_string_result("log")),"flag 'log': is required");
+        let aa = parse_args(spec,&["--optimize","--log"]);
+        assert_eq!(aa.get_integer("optimize"),1);
+        assert_eq!(aa.get_string("log"),"app.log");
+        let aa = parse_args(spec,&["--optimize","2","--log","-v"]);
+        assert_eq!(aa.get_integer("optimize"),2);
+        assert_eq!(aa.get_string("log"),"app.log");
+        assert!(aa.get_bool("verbose"));
+        let aa = parse_args(spec,&["-vO"]);
+        assert_eq!(aa.get_integer("optimize"),1);
+        let aa = parse_args(spec,&["-O3"]);
+        assert_eq!(aa.get_integer("optimize"),3);
+    }
+
+    #[test]
+    fn test_describe_flag() {
+        let mut args = Args::new("
+            -o, --output (default 'stdout') Write results to this file.
+            -n (1..10) count
+            -v
+            -I, --include... (string)
+            <file> (string) input file
+        ");
+        args.parse_spec().expect("spec failed");
+        assert_eq!(args.describe_flag("output"),
+            "--output, -o  [string, optional, default: \"stdout\"]\n  Write results to this file.\n");
+        assert_eq!(args.describe_flag("n"), "-n  [integer, required, range 1..10]\n  count\n");
+        assert_eq!(args.describe_flag("v"), "-v  [bool, optional, default: false]\n");
+        assert_eq!(args.describe_flag("include"), "--include, -I  [string, optional, multiple]\n");
+        assert_eq!(args.describe_flag("file"), "<file>  [string, required]\n  input file\n");
+        assert_eq!(err(args.describe_flag_result("nope")), "no long flag 'nope'");
+    }
+
+    #[test]
+    fn test_set_default() {
+        let spec = "
+            --dir (string)
+            --count (integer default 1)
+            -v, --verbose
+        ";
+        let mut args = Args::new(spec);
+        args.parse_spec().expect("spec failed");
+        args.set_default("dir","/tmp/out").expect("set_default failed");
+        args.set_default("count","10").expect("set_default failed");
+        args.set_default("nope","x").expect("unknown flags are ignored");
+        assert!(err(args.set_default("count","ten")).starts_with("flag 'count': bad default: can't convert 'ten' to integer"));
+        assert!(args.set_default("verbose","yes").is_err());
+        args.try_parse_from(["--count","5"]).expect("scan failed");
+        assert_eq!(args.get_string("dir"),"/tmp/out");
+        assert_eq!(args.get_integer("count"),5);
+        args.set_default("count","20").expect("set_default failed");
+        assert_eq!(args.get_integer("count"),5);
+        // the spec's defaults are restored on a fresh parse
+        args.reparse(empty_strings()).expect("reparse failed");
+        assert_eq!(args.get_integer("count"),1);
+        assert_eq!(err(args.get_string_result("dir")),"flag 'dir': is required");
+
+        // a new default must still be within the declared range
+        let mut args = Args::new("--count (1..10 default 5)");
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.set_default("count","500")),"flag 'count': default 500 is outside declared range 1..10");
+        let mut config = HashMap::new();
+        config.insert("count".to_string(),"500".to_string());
+        assert_eq!(err(args.merge_defaults_from_hashmap(&config)),"flag 'count': default 500 is outside declared range 1..10");
+        args.try_parse_from(empty_strings()).expect("scan failed");
+        assert_eq!(args.get_integer("count"),5);
+    }
+
+    #[test]
+    fn test_json() {
+        let spec = "
+            -v, --verbose
+            -k (default 10)
+            --save (default 'out.txt')
+            --scale (default 0.5)
+            -I, --include... (string)
+            <name> (string)
+        ";
+        let aa = parse_args(spec,&["-I","a\"b","-I","c\\d\n","x"]);
+        assert_eq!(aa.get_as_json(),
+            r#"{"verbose":false,"k":10,"save":"out.txt","scale":0.5,"include":["a\"b","c\\d\n"],"name":"x","help":false}"#);
+        let aa = parse_args(spec,&[]);
+        assert!(aa.get_as_json().contains(r#""include":[],"name":null"#));
+    }
+
+    #[test]
+    fn test_unicode_flags() {
+        let spec = "
+            -é, --日本語 (string)
+            --größe (integer default 1)
+            --٣d
+        ";
+        let mut aa = parse_args(spec,&["-é","hello","--größe","2","--٣d"]);
+        assert_eq!(aa.get_string("日本語"),"hello");
+        assert_eq!(aa.get_integer("größe"),2);
+        assert!(aa.get_bool("٣d"));
+        let decls = aa.declarations("");
+        assert!(decls.contains("let 日本語 = args.get_string(\"日本語\");"));
+        assert!(decls.contains("let c_٣d = args.get_bool(\"٣d\");"));
+    }
+
+    #[test]
+    fn test_by_pos() {
+        let aa = parse_args(SIMPLE,&["-v","in","a","b"]);
+        assert_eq!(aa.get_string_by_pos(1),"in");
+        assert_eq!(aa.get_by_pos(2).as_array().unwrap().len(),2);
+        assert_eq!(err(aa.get_string_by_pos_result(2)),"argument #2 'out': not a string, but array of string");
+        assert_eq!(err(aa.get_by_pos_result(3)),"no arg #3");
+        assert_eq!(err(aa.get_by_pos_result(0)),"no arg #0");
+    }
+
+    #[test]
+    fn test_dedent() {
+        let text = "
+    Program description
+      -v  verbose
+          (a continuation)
+  é short line
+\u{3000}   -k  ideographic space
+";
+        assert_eq!(strutil::dedent(text),
+            "Program description\n  -v  verbose\n      (a continuation)\né short line\n-k  ideographic space\n");
+    }
+
+    #[test]
+    fn test_from_env_only() {
+        let spec = "
+            -v, --verbose
+            --output-dir (string)
+            --lines (default 10)
+            -I, --include... (string)
+        ";
+        let mut args = Args::new(spec).with_program_name("lapp-test");
+        ::std::env::set_var("LAPP_TEST_VERBOSE","yes");
+        ::std::env::set_var("LAPP_TEST_OUTPUT_DIR","/tmp/out");
+        ::std::env::set_var("LAPP_TEST_INCLUDE","a 'b c'");
+        args.from_env_only().expect("env failed");
+        assert!(args.get_bool("verbose"));
+        assert_eq!(args.get_string("output-dir"),"/tmp/out");
+        assert_eq!(args.get_integer("lines"),10);
+        assert_eq!(args.get_strings("include"),["a","b c"]);
+
+        let mut args = Args::new(spec).with_program_name("lapp-test2");
+        assert_eq!(err(args.from_env_only()),"required environment variable LAPP_TEST2_OUTPUT_DIR is not set");
+    }
+
+    #[test]
+    fn test_double_multiple() {
+        let mut aa = Args::new("--ports... (integer...)\n");
+        assert_eq!(err(aa.parse_spec()),"cannot use both '...' suffix and '...' inside type specifier: flag 'ports'\nat line: '--ports... (integer...)'");
+        assert!(Args::new("--ports... (integer)\n--files (string...)\n").parse_spec().is_ok());
+    }
+
+    #[test]
+    fn test_value_from_strings() {
+        let mut aa = Args::new(SIMPLE);
+        aa.parse_spec().unwrap();
+        aa.flags_by_long("include").unwrap().set_value_from_strings(&["a","b"]).unwrap();
+        assert_eq!(aa.flags_by_long_ref("include").unwrap().value.as_array().unwrap().len(),2);
+        let p = aa.flags_by_long("p").unwrap();
+        p.set_value_from_strings(&["10","20 30"]).unwrap();
+        assert_eq!(p.value.as_array().unwrap().len(),3);
+
+        let mut aa = Args::new("--n... (integer)\n");
+        aa.parse_spec().unwrap();
+        let n = aa.flags_by_long("n").unwrap();
+        assert_eq!(err(n.set_value_from_strings(&["1","x"])),
+            "flag 'n': bad value at index 1: can't convert 'x' to integer - invalid digit found in string");
+    }
+
+    #[test]
+    fn test_positional_names() {
+        let aa = parse_args(SIMPLE,&["in"]);
+        assert_eq!(aa.positional_names(),["in","out"]);
+        assert_eq!(aa.get_flag_position("out"),Some(2));
+        assert_eq!(aa.get_flag_position("verbose"),None);
+        assert!(aa.is_positional("in"));
+        assert!(! aa.is_positional("include"));
+        assert_eq!(err(aa.get_flag_position_result("nope")),"no long flag 'nope'");
+        let aa = parse_args("-v verbose\n",&[]);
+        assert!(aa.positional_names().is_empty());
+        assert_eq!(aa.positional_count(),0);
+
+        let mut aa = Args::new(SIMPLE);
+        aa.parse_spec().unwrap();
+        let names: Vec<&str> = aa.names().collect();
+        assert_eq!(names,["verbose","k","output","p","include","in","out","help"]);
+        assert_eq!(aa.positional_count(),2);
+        let shorts: String = aa.short_names().collect();
+        assert_eq!(shorts,"vkopIh");
+        assert!(aa.is_short_flag('I'));
+        assert!(! aa.is_short_flag('x'));
+        assert_eq!(aa.get_flag_names_of_type("string"),["in","include","out","output"]);
+        assert_eq!(aa.get_flag_names_of_type("array of integer"),["p"]);
+        assert_eq!(aa.get_flag_names_of_type("bool"),["help","k","verbose"]);
+        assert!(aa.get_flag_names_of_type("frodo").is_empty());
+    }
+
+    #[test]
+    fn test_number() {
+        let spec = "
+            --scale (number default 1)
+            --xs... (number)
+            --bad (number)
+        ";
+        let aa = parse_args(spec,&["--xs","10","--xs","2.5","--bad","ten"]);
+        assert_eq!(aa.get_number("scale"),1.0);
+        assert_eq!(aa.get_numbers("xs"),[10.0,2.5]);
+        assert_eq!(aa.get_float("scale"),1.0);
+        assert_eq!(err(aa.get_number_result("bad")),"flag 'bad': can't convert 'ten' to number");
+        assert_eq!(aa.get_flag_type("scale").unwrap().short_name(),"number");
+
+        // values keep their f64 precision
+        let aa = parse_args(spec,&["--scale","0.1","--xs","16777217"]);
+        assert_eq!(aa.get_number("scale"),0.1);
+        assert_eq!(aa.get_numbers("xs"),[16777217.0]);
+        let aa = parse_args("--xs... (float)",&["--xs","0.5"]);
+        assert_eq!(aa.get_numbers("xs"),[0.5]);
+    }
+
+    #[test]
+    fn test_dump() {
+        let aa = parse_args("-n (default 10)\n--dir (path)\n<in> (string)\n",&["--dir","tmp","x"]);
+        let mut out = Vec::new();
+        aa.dump_to_writer(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+            "  --n  integer  Int(10)\n  --dir  path  Path(\"tmp\")\n  --in  string  Str(\"x\")\n  --help  bool  Bool(false)\n");
+    }
+
+    #[test]
+    fn test_bool_default() {
+        let spec = "
+            --cache (bool default true)
+            --quiet (bool)
+            -v, --verbose
+        ";
+        let aa = parse_args(spec,&[]);
+        assert!(aa.get_bool("cache"));
+        assert!(! aa.get_bool("quiet"));
+        let aa = parse_args(spec,&["--no-cache","--quiet"]);
+        assert!(! aa.get_bool("cache"));
+        assert!(aa.get_bool("quiet"));
+        assert!(! aa.get_bool("verbose"));
+        // only flags which are on by default can be switched off
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.try_parse_from(["--no-verbose"])),"no long flag 'no-verbose'");
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.try_parse_from(["--no-help"])),"no long flag 'no-help'");
+        assert!(err(Args::new("--cache (bool default yes)").parse_spec()).starts_with("bool default must be true or false, not 'yes'"));
+    }
+
+    #[test]
+    fn test_range_default() {
+        let aa = parse_args("--timeout (1..3600 default 30)\n--scale (0.0..1.0 default 0.5)\n",&[]);
+        assert_eq!(aa.get_integer("timeout"),30);
+        assert_eq!(aa.get_float("scale"),0.5);
+        let aa = parse_args("--timeout (1..3600 default 30)\n",&["--timeout","60"]);
+        assert_eq!(aa.get_integer("timeout"),60);
+        let mut aa = Args::new("--timeout (1..3600 default 5000)\n");
+        assert!(err(aa.parse_spec()).starts_with("flag 'timeout': default 5000 is outside declared range 1..3600"));
+        // a default of the wrong type is not let through
+        let mut aa = Args::new("--timeout (integer 1..3600 default soon)\n");
+        assert!(err(aa.parse_spec()).starts_with("flag 'timeout': default soon is not integer"));
+        let mut aa = Args::new("--scale (float 0..1 default -0.5)\n");
+        assert!(err(aa.parse_spec()).starts_with("flag 'scale': default -0.5 is not float"));
+        // other constraints apply to the default as well
+        let mut aa = Args::new("--count (integer positive default 0)\n");
+        assert!(err(aa.parse_spec()).starts_with("flag 'count': 0 is not positive"));
+    }
+
+    #[test]
+    fn test_help_sections() {
+        let spec = "
+            -v, --verbose
+            [group: Output]
+            -o, --output (default stdout)
+            --json
+            [group: Input]
+            <file> (string)
+        ";
+        let aa = parse_args(spec,&["x"]);
+        let sections = aa.help_sections();
+        let names: Vec<(&str,Vec<&str>)> = sections.iter()
+            .map(|s| (s.0.as_str(), s.1.iter().map(|f| f.name).collect()))
+            .collect();
+        assert_eq!(names,[("",vec!["verbose","help"]),("Output",vec!["output","json"]),("Input",vec!["file"])]);
+        assert_eq!(sections[1].1[0].group,"Output");
+
+        let section = |name: &str| {
+            let mut out = Vec::new();
+            aa.print_help_section(&mut out,name).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(section("Output"),"Output:\n  --output, -o  outfile\n  --json        bool\n");
+        assert_eq!(section("Frodo"),"no help section 'Frodo'; sections are: Output, Input\n");
+    }
+
+    #[test]
+    fn test_exactly_one_of() {
+        let spec = "
+            --json (exactly-one-of 'format')
+            --csv (exactly-one-of 'format')
+            --sep (exactly-one-of ',' ';' 'tab')
+        ";
+        let aa = parse_args(spec,&["--csv","--sep","tab"]);
+        assert!(aa.get_bool("csv"));
+        assert!(! aa.get_bool("json"));
+        assert_eq!(aa.get_string("sep"),"tab");
+
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        assert_eq!(err(aa.try_parse_from(["--sep",","])),
+            "group 'format': exactly one of --json, --csv must be given");
+        aa.clear();
+        assert_eq!(err(aa.try_parse_from(["--csv","--json","--sep",","])),
+            "group 'format': exactly one of --json, --csv must be given");
+        aa.clear();
+        assert_eq!(err(aa.try_parse_from(["--csv","--sep","|"])),
+            "flag 'sep': '|' is not one of ',', ';', 'tab'");
+    }
+
+    #[test]
+    fn test_bool_parse_string() {
+        assert!(! Type::Bool.parse_string("false").unwrap().as_bool().unwrap());
+        let mut aa = Args::new("-v, --verbose\n-q, --quiet\n");
+        aa.parse_spec().unwrap();
+        aa.flags_by_long("verbose").unwrap().set_value_from_string("true").unwrap();
+        aa.flags_by_long("quiet").unwrap().set_value_from_string("maybe").unwrap();
+        assert!(aa.get_bool("verbose"));
+        assert_eq!(err(aa.get_bool_result("quiet")),
+            "flag 'quiet': can't convert 'maybe' to bool - provided string was not `true` or `false`");
+    }
+
+    #[test]
+    fn test_keyval() {
+        let spec = "
+            -D, --define (keyval)
+            --opt (keyval)
+        ";
+        let aa = parse_args(spec,&["-Dlevel=42","--define","name=joe","-D","x=1.5"]);
+        let defs = aa.get_string_map("define");
+        assert_eq!(defs["level"],"42");
+        assert_eq!(defs["name"],"joe");
+        assert!(aa.get_string_map("opt").is_empty());
+        let nums = aa.get_map_result("define",|v| v.as_int());
+        assert_eq!(err(nums),"flag 'define': key 'name': not a integer, but string");
+        let aa = parse_args(spec,&["-Da=1","-Db=-2"]);
+        let ints = aa.get_map_result("define",|v| v.as_int()).unwrap();
+        assert_eq!(ints["b"],-2);
+        let aa = parse_args(spec,&["-Da"]);
+        assert_eq!(err(aa.get_string_map_result("define")),"flag 'define': 'a' is not a key=value pair");
+        assert_eq!(aa.flags_by_long_ref("define").unwrap().getter_name(),"args.get_string_map(\"define\")");
+    }
+
+    #[test]
+    fn test_error_exports() {
+        fn check(n: i32) -> ::Result<i32> {
+            if n < 0 { return ::make_error(format!("{} is negative",n)); }
+            Ok(n)
+        }
+        match check(-1) {
+            Err(::Error(msg)) => assert_eq!(msg,"-1 is negative"),
+            Ok(_) => panic!("expected an error")
+        }
+        let e: Box<dyn std::error::Error> = Box::new(::LappError("boom".into()));
+        assert_eq!(e.to_string(),"boom");
+    }
+
+    #[test]
+    fn test_optional_positional() {
+        let spec = "
+            <in> (string)
+            <out>? (string)
+            <log> (string optional)
+        ";
+        let aa = parse_args(spec,&["a"]);
+        assert_eq!(aa.get_string("in"),"a");
+        assert!(! aa.flag_present("out"));
+        assert_eq!(err(aa.get_string_result("out")),"argument #2 'out': was not given");
+        assert_eq!(aa.required_flags(),["in"]);
+        let aa = parse_args(spec,&["a","b","c"]);
+        assert_eq!(aa.get_string("out"),"b");
+        assert_eq!(aa.get_string("log"),"c");
+        assert!(aa.describe_flag("out").starts_with("<out>  [string, optional]"));
+    }
+
+    #[test]
+    fn test_hyphenated_flag() {
+        let spec = "--flag-name (string)\n";
+        let mut aa = parse_args(spec,&["--flag-name","x"]);
+        assert_eq!(aa.get_string("flag-name"),"x");
+        let decls = aa.declarations("");
+        assert!(decls.contains("let flag_name = args.get_string(\"flag-name\");"));
+    }
+
+    #[test]
+    fn test_validate_all() {
+        let spec = "
+            -v, --verbose
+            -v, --very
+            --count (integer default 1)
+            --n (foo)
+        ";
+        let errors = Args::new(spec).parse_spec_and_validate();
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors,[
+            "short flag 'v' already defined: flag 'very'\nat line: '            -v, --very'",
+            "not a known type foo\nat line: '            --n (foo)'"
+        ]);
+        assert!(Args::new(SIMPLE).parse_spec_and_validate().is_empty());
+        assert_eq!(err(Args::new(spec).parse_spec()),errors[0]);
+    }
+
+    #[test]
+    fn test_usage_repr() {
+        let spec = "
+            -v, --verbose
+            -q
+            -o, --output (string)
+            -n (integer)
+            --lines (default 10)
+            -I, --include... (string)
+            --ports (integer...)
+            <file> (string)
+            <extra>? (string)
+            <rest> (string...)
+        ";
+        let mut aa = Args::new(spec).with_program_name("prog");
+        aa.parse_spec().unwrap();
+        let reprs: Vec<String> = aa.flags.iter().map(|f| f.usage_repr().unwrap()).collect();
+        assert_eq!(reprs,["[--verbose]","[-q]","--output OUTPUT","-n N","[--lines LINES]",
+            "[--include INCLUDE...]","[--ports PORTS]","<file>","[<extra>]","[<rest>...]","[--help]"]);
+        assert_eq!(aa.usage_line(),"prog [--verbose] [-q] --output OUTPUT -n N [--lines LINES] \
+            [--include INCLUDE...] [--ports PORTS] <file> [<extra>] [<rest>...]");
+
+        let spec = "
+            File input and output
+            with optional output
+
+            -v, --verbose
+            <in> (string)
+        ";
+        let mut aa = Args::new(spec).with_program_name("myapp");
+        aa.parse_spec().unwrap();
+        assert_eq!(aa.short_usage(),"File input and output\nwith optional output\nUsage: myapp [--verbose] <in>");
+    }
+
+    #[test]
+    fn test_bool_string() {
+        let spec = "
+            --color (bool-string default on)
+            --cache (bool-string)
+        ";
+        let aa = parse_args(spec,&["--cache","No"]);
+        assert!(aa.get_bool("color"));
+        assert!(! aa.get_bool("cache"));
+        let aa = parse_args(spec,&["--color","0","--cache","Y"]);
+        assert!(! aa.get_bool("color"));
+        assert!(aa.get_bool("cache"));
+        let aa = parse_args(spec,&["--cache","maybe"]);
+        assert!(err(aa.get_bool_result("cache")).starts_with("flag 'cache': can't convert 'maybe' to bool"));
+    }
+
+    #[test]
+    fn test_print_help() {
+        let mut aa = Args::new("
+            A test program
+              -v, --verbose  say more
+              <file> (string) input
+        ").with_program_name("prog");
+        aa.parse_spec().unwrap();
+        let mut out = Vec::new();
+        aa.print_help(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("A test program\n  -v, --verbose  say more\n"));
+        assert!(text.ends_with("<file> (string) input\n\nUsage: prog [--verbose] <file>\n"));
+
+        aa.set_help_text("plugins: a, b");
+        aa.set_help_text("config: ~/.test");
+        let mut out = Vec::new();
+        aa.print_help(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.ends_with("<file>\n\nplugins: a, b\nconfig: ~/.test\n"));
+    }
+
+    #[test]
+    fn test_quoted_defaults() {
+        let spec = r#"
+            --single (default 'foo=bar')
+            --double (default "foo=bar")
+            --plain (default foo=bar)
+            --spaces (string default "a (b) c")
+            --apos (default "it's")
+        "#;
+        let aa = parse_args(spec,&[]);
+        assert_eq!(aa.get_string("single"),"foo=bar");
+        assert_eq!(aa.get_string("double"),"foo=bar");
+        assert_eq!(aa.get_string("plain"),"foo=bar");
+        assert_eq!(aa.get_string("spaces"),"a (b) c");
+        assert_eq!(aa.get_string("apos"),"it's");
+    }
+
+    #[test]
+    fn test_sign_constraints() {
+        let spec = "
+            --count (non-negative)
+            --scale (float positive default 1.0)
+            --n (integer positive)
+        ";
+        let aa = parse_args(spec,&["--count","0","--n","3"]);
+        assert_eq!(aa.get_integer("count"),0);
+        assert_eq!(aa.get_float("scale"),1.0);
+        assert_eq!(aa.get_integer("n"),3);
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        assert_eq!(err(aa.try_parse_from(["--count","-1"])),"flag 'count': -1 is not non-negative");
+        aa.clear();
+        assert_eq!(err(aa.try_parse_from(["--count","1","--scale","0"])),"flag 'scale': 0 is not positive");
+        assert!(aa.describe_flag("n").contains("positive"));
+        assert!(Args::new("--s (string positive)").parse_spec().is_err());
+    }
+
+    #[test]
+    fn test_three_dashes() {
+        let mut aa = Args::new("---very-long-flag-name (string)\n");
+        assert_eq!(err(aa.parse_spec()),
+            "spec error: three dashes found; did you mean '--very-long-flag-name'?\nat line: '---very-long-flag-name (string)'");
+    }
+
+    #[test]
+    fn test_email() {
+        let spec = "--admin (email)\n";
+        let aa = parse_args(spec,&["--admin","admin@example.com"]);
+        assert_eq!(aa.get_string("admin"),"admin@example.com");
+        for bad in &["notanemail","@example.com","a@b@c.com","a@example","a@.com","a b@example.com"] {
+            let aa = parse_args(spec,&["--admin",bad]);
+            assert_eq!(err(aa.get_string_result("admin")),
+                format!("flag 'admin': '{}' does not look like a valid email address",bad));
+        }
+
+        // defaults may be quoted, and are checked
+        let aa = parse_args("--admin (email default 'a@b.com')\n--root (email default root@b.com)\n",&[]);
+        assert_eq!(aa.get_string("admin"),"a@b.com");
+        assert_eq!(aa.get_string("root"),"root@b.com");
+        let mut args = Args::new("--admin (email default 'nobody')");
+        assert!(err(args.parse_spec()).starts_with("'nobody' does not look like a valid email address"));
+        // as may password defaults; only the spec is parsed here, so nothing is asked for
+        let mut args = Args::new("--password (password default 'guest')");
+        args.parse_spec().unwrap();
+        assert_eq!(args.flags[0].defval.as_str().unwrap(),"guest");
+    }
+
+    #[test]
+    fn test_to_argv() {
+        let aa = parse_args(SIMPLE,&["-vI.","--include","lib","-p","1 2","-o","out.txt","in","a","b"]);
+        let argv = aa.to_argv();
+        assert_eq!(argv,["--verbose","--output","out.txt","-p","1 2","--include",".","--include","lib","in","a","b"]);
+        let bb = parse_args(SIMPLE,&argv.iter().map(|s| s.as_str()).collect::<Vec<_>>());
+        assert_eq!(aa.get_as_json(),bb.get_as_json());
+
+        let aa = parse_args(SIMPLE,&["in","--","-x"]);
+        assert_eq!(aa.to_argv(),["--","in","-x"]);
+
+        let mut aa = Args::new("--count (integer default 1)\n--cache (bool default true)\n");
+        aa.parse_spec().unwrap();
+        aa.set_default("count","5").unwrap();
+        aa.try_parse_from(["--no-cache"]).unwrap();
+        assert_eq!(aa.to_argv(),["--count","5","--no-cache"]);
+    }
+
+    #[test]
+    fn test_synopsis_hide() {
+        let spec = "
+            -v, --verbose
+            --debug (synopsis-hide)
+            --trace (string synopsis-hide)
+            <file> (string)
+        ";
+        let aa = parse_args(spec,&["--debug","x"]).with_program_name("prog");
+        assert!(aa.get_bool("debug"));
+        assert_eq!(aa.usage_line(),"prog [--verbose] <file>");
+        assert_eq!(aa.short_usage(),"Usage: prog [--verbose] <file>");
+        let mut out = Vec::new();
+        aa.print_help(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("--trace"));
+    }
+
+    #[test]
+    fn test_empty_value() {
+        let spec = "--output (string)\n-v, --verbose\n";
+        let aa = parse_args(spec,&["--output=","-v"]);
+        assert_eq!(aa.get_string("output"),"");
+        assert!(aa.get_bool("verbose"));
+        let aa = parse_args(spec,&["--output","-v"]);
+        assert_eq!(aa.get_string("output"),"-v");
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        assert_eq!(err(aa.try_parse_from(["--output"])),"no value for flag 'output'");
+    }
+
+    #[test]
+    fn test_get_or_else() {
+        let spec = "
+            --name (string optional)
+            --port (integer optional)
+            --ratio (float default 0.5)
+        ";
+        let aa = parse_args(spec,&["--port","8080"]);
+        let called = ::std::cell::Cell::new(false);
+        assert_eq!(aa.get_integer_or_else("port",|| { called.set(true); 80 }), 8080);
+        assert!(! called.get());
+        assert_eq!(aa.get_string_or_else("name",|| "anon".into()), "anon");
+        assert_eq!(aa.get_float_or_else("ratio",|| 1.0), 0.5);
+        assert_eq!(aa.get_or_else::<u16,_>("port",|| 80), 8080);
+        let aa = parse_args(spec,&[]);
+        assert_eq!(aa.get_or_else::<u16,_>("port",|| 80), 80);
+    }
+
+    #[test]
+    fn test_infile_lines() {
+        let aa = parse_args("--in (infile-lines)",&["--in","Cargo.toml"]);
+        let lines = aa.get_strings("in");
+        assert_eq!(lines[0], "[package]");
+        assert!(lines.len() > 5);
+
+        let aa = parse_args("--in (infile-lines default 'no-such-file')",&[]);
+        assert!(err(aa.get_strings_result("in")).starts_with("flag 'in': can't open 'no-such-file' for reading"));
+
+        let mut args = Args::new("--in (infile-lines)");
+        assert!(args.declarations("").contains("args.get_strings(\"in\")"));
+    }
+
+    #[test]
+    fn test_validate_arg_count() {
+        let spec = "
+            <first> (string default 'a')
+            <rest> (string...)
+        ";
+        let aa = parse_args(spec,&["x","y","z"]);
+        assert!(aa.validate_arg_count_result(2,None).is_ok());
+        assert!(aa.validate_arg_count_result(3,Some(3)).is_ok());
+        assert_eq!(err(aa.validate_arg_count_result(1,Some(2))),"expected at most 2 arguments, got 3");
+        let aa = parse_args(spec,&[]);
+        assert_eq!(err(aa.validate_arg_count_result(1,None)),"expected at least 1 arguments, got 0");
+    }
+
+    #[test]
+    fn test_warn_unused() {
+        let mut aa = parse_args("-v, --verbose\n--out (default 'x')\n--n (integer optional)\n",&[]);
+        aa.set_warn_unused(true);
+        assert_eq!(aa.unused_flags(),["verbose","out","n"]);
+        aa.get_bool("verbose");
+        assert!(! aa.flag_present("n"));
+        assert_eq!(aa.unused_flags(),["out"]);
+        aa.set_warn_unused(false);
+    }
+
+    #[test]
+    fn test_default_env_vars() {
+        env::set_var("LAPP_TEST_HOST","example.com");
+        env::remove_var("LAPP_TEST_NO_PORT");
+        let spec = "
+            --host (string default '${LAPP_TEST_HOST:-localhost}')
+            --port (integer default ${LAPP_TEST_NO_PORT:-8080})
+            --url (default 'http://${LAPP_TEST_HOST}/')
+        ";
+        let aa = parse_args(spec,&[]);
+        assert_eq!(aa.get_string("host"),"example.com");
+        assert_eq!(aa.get_integer("port"),8080);
+        assert_eq!(aa.get_string("url"),"http://example.com/");
+
+        let mut args = Args::new("--port (integer default ${LAPP_TEST_NO_PORT})");
+        assert_eq!(err(args.parse_spec()),"environment variable 'LAPP_TEST_NO_PORT' is not set\nat line: '--port (integer default ${LAPP_TEST_NO_PORT})'");
+        let mut args = Args::new("--x (default '${A:-${B}}')");
+        assert!(err(args.parse_spec()).starts_with("bad variable"));
+        let mut args = Args::new("--port (integer default ${LAPP_TEST_NO_PORT:-})");
+        assert!(err(args.parse_spec()).starts_with("default is empty"));
+        // a variable's value is not expanded again
+        env::set_var("LAPP_TEST_SELF","${LAPP_TEST_SELF}");
+        let aa = parse_args("--x (string default '${LAPP_TEST_SELF}')",&[]);
+        assert_eq!(aa.get_string("x"),"${LAPP_TEST_SELF}");
+    }
+
+    #[test]
+    fn test_help_table() {
+        let aa = parse_args("
+            -v, --verbose verbose flag
+            -n, --count (1..10 default 1) how many
+            <files> (string...)
+        ",&["a"]);
+        assert_eq!(aa.help_table(),
+"  --verbose, -v  bool                   verbose flag
+  --count, -n    integer (range 1..10)  how many
+  <files>        string...
+  --help, -h     bool                   this help
+");
+    }
+
+    #[test]
+    fn test_args_macro() {
+        let spec = lapp_spec! {
+            verbose: bool = false,
+            output: String = "stdout",
+            count: i32 required,
+            ratio: f32 = 0.5,
+            xs: Vec<i32>
+        };
+        let aa = parse_args(spec,&["--count","2","--xs","1","--xs","3"]);
+        assert!(! aa.get_bool("verbose"));
+        assert_eq!(aa.get_string("output"),"stdout");
+        assert_eq!(aa.get_integer("count"),2);
+        assert_eq!(aa.get_float("ratio"),0.5);
+        assert_eq!(aa.get_integers("xs"),[1,3]);
+    }
+
+    #[derive(Debug,PartialEq)]
+    struct Port(u16);
+
+    impl<'a> TryFrom<&'a Value> for Port {
+        type Error = String;
+
+        fn try_from(v: &'a Value) -> ::std::result::Result<Port,String> {
+            let n = v.as_int().map_err(|e| e.0)?;
+            if n > 0 && n < 65536 {
+                Ok(Port(n as u16))
+            } else {
+                Err(format!("{} is not a port number",n))
+            }
+        }
+    }
+
+    impl TryFrom<Value> for Port {
+        type Error = LappError;
+
+        fn try_from(v: Value) -> Result<Port> {
+            Port::try_from(&v).map_err(LappError)
+        }
+    }
+
+    #[test]
+    fn test_get_typed_result() {
+        let spec = "--port (integer default 8080)\n--name (default 'x')\n";
+        let aa = parse_args(spec,&[]);
+        assert_eq!(aa.get_typed_result::<Port>("port").unwrap(),Port(8080));
+        let port: Port = aa.get_typed("port");
+        assert_eq!(port,Port(8080));
+        let aa = parse_args(spec,&["--port","0"]);
+        assert_eq!(err(aa.get_typed_result::<Port>("port")),"flag 'port': 0 is not a port number");
+        assert_eq!(err(aa.get_typed_result::<Port>("name")),"flag 'name': not a integer, but string");
+
+        let spec = "--ports... (integer)\n--list (integer...)\n";
+        let aa = parse_args(spec,&["--ports","80","--ports","443","--list","1 2"]);
+        assert_eq!(aa.get_typed_array::<Port>("ports"),[Port(80),Port(443)]);
+        assert_eq!(aa.get_typed_array::<Port>("list"),[Port(1),Port(2)]);
+        let aa = parse_args(spec,&["--ports","80","--ports","0","--list","x"]);
+        assert_eq!(err(aa.get_typed_array_result::<Port>("ports")),"flag 'ports': 0 is not a port number");
+        assert!(err(aa.get_typed_array_result::<Port>("list")).starts_with("flag 'list': can't convert 'x' to integer"));
+    }
+
+    #[test]
+    fn test_default_from() {
+        let spec = "
+            --input-dir (string default '.')
+            --output-dir (string default_from 'input-dir')
+            --backup-dir (string default_from output-dir)
+        ";
+        let aa = parse_args(spec,&["--input-dir","src"]);
+        assert_eq!(aa.get_string("output-dir"),"src");
+        assert_eq!(aa.get_string("backup-dir"),"src");
+        let aa = parse_args(spec,&["--output-dir","out"]);
+        assert_eq!(aa.get_string("output-dir"),"out");
+        assert_eq!(aa.get_string("backup-dir"),"out");
+
+        let mut args = Args::new("--a (string default_from b)\n--b (string default_from a)\n");
+        assert_eq!(err(args.parse_spec()),"default_from cycle: a -> b -> a");
+        let mut args = Args::new("--a (string default_from b)\n--b (integer default 1)\n");
+        assert_eq!(err(args.parse_spec()),"flag 'a': default_from 'b' which is integer, not string");
+        let mut args = Args::new("--a (string default_from c)\n");
+        assert_eq!(err(args.parse_spec()),"flag 'a': default_from unknown flag 'c'");
+    }
+
+    #[test]
+    fn test_unclosed_specifier() {
+        let mut args = Args::new("--n (integer 1..10 default 5\n");
+        assert_eq!(err(args.parse_spec()),
+            "cannot find ')' in 'integer 1..10 defaul'\nat line: '--n (integer 1..10 default 5'");
+        let mut args = Args::new("<file (string)\n");
+        assert!(err(args.parse_spec()).starts_with("cannot find '>' in 'file (string)'"));
+    }
+
+    #[test]
+    fn test_parse_spec_from_file() {
+        let dir = env::temp_dir().join(format!("lapp-spec-file-{}",::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("test.lapp");
+        fs::write(&file,"A test\n  -v, --verbose\n  --n (integer default 2)\n").unwrap();
+        let mut args = Args::new("");
+        args.parse_spec_from_file(&file).unwrap();
+        args.try_parse_from(["-v"]).unwrap();
+        assert!(args.get_bool("verbose"));
+        assert_eq!(args.get_integer("n"),2);
+
+        fs::write(&file,"A test\n  -v, --verbose\n  --n (frodo)\n").unwrap();
+        let mut args = Args::new("");
+        assert_eq!(err(args.parse_spec_from_file(&file)),
+            format!("spec file '{}', line 3: not a known type frodo",file.display()));
+        let mut args = Args::new("");
+        assert!(err(args.parse_spec_from_file(&dir.join("nope.lapp"))).starts_with("cannot read spec file"));
+    }
+
+    #[test]
+    fn test_prompt_for() {
+        let aa = parse_args("--port (integer optional)",&[]);
+        let mut out = Vec::new();
+        let mut input = io::Cursor::new("x\n8080\n");
+        let port: u16 = aa.prompt_for("port","port? ",&mut input,&mut out).unwrap();
+        assert_eq!(port,8080);
+        assert_eq!(String::from_utf8(out).unwrap(),"port? invalid digit found in string\nport? ");
+
+        let mut input = io::Cursor::new("a\nb\nc\n8080\n");
+        assert_eq!(err(aa.prompt_for::<u16,_,_>("port","",&mut input,&mut Vec::new())),
+            "flag 'port': invalid digit found in string");
+        let mut input = io::Cursor::new("");
+        assert_eq!(err(aa.prompt_for::<u16,_,_>("port","",&mut input,&mut Vec::new())),"flag 'port': is required");
+    }
+
+    #[test]
+    fn test_copy_flag_value() {
+        let spec = "
+            --input (string default 'in.txt')
+            --file (string optional) old name for --input
+            --count (integer default 1)
+        ";
+        let mut aa = parse_args(spec,&["--file","old.txt"]);
+        aa.copy_flag_value("file","input").unwrap();
+        assert_eq!(aa.get_string("input"),"old.txt");
+        assert!(aa.flag_given("input"));
+
+        let mut aa = parse_args(spec,&["--input","new.txt"]);
+        aa.copy_flag_value("file","input").unwrap();
+        assert_eq!(aa.get_string("input"),"new.txt");
+        assert_eq!(err(aa.copy_flag_value("count","input")),"cannot copy flag 'count' (integer) to 'input' (string)");
+        assert_eq!(err(aa.copy_flag_value("nope","input")),"no long flag 'nope'");
+    }
+
+    #[test]
+    fn test_password() {
+        let spec = "
+            --user (string default 'me')
+            --password (password)
+        ";
+        // not on a terminal, so it's just a required string
+        let aa = parse_args(spec,&["--password","secret"]);
+        assert_eq!(aa.get_string("password"),"secret");
+        assert!(aa.get_as_json().contains("\"password\":\"***\""));
+        let mut out = Vec::new();
+        aa.dump_to_writer(&mut out).unwrap();
+        assert!(! String::from_utf8(out).unwrap().contains("secret"));
+
+        // with the prompt, this would wait for input on a terminal
+        #[cfg(not(feature = "password-prompt"))]
+        {
+            let aa = parse_args(spec,&[]);
+            assert_eq!(err(aa.get_string_result("password")),"flag 'password': is required");
+        }
+
+        // what was typed at the prompt becomes the value
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        aa.read_passwords_with(|name| Ok(Some(format!("{}-typed",name)))).unwrap();
+        assert_eq!(aa.get_string("password"),"password-typed");
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        aa.read_passwords_with(|_| Ok(None)).unwrap();
+        aa.flags_by_long("password").unwrap().check().unwrap();
+        assert_eq!(err(aa.get_string_result("password")),"flag 'password': is required");
+    }
+
+    #[test]
+    fn test_merge_defaults_from_hashmap() {
+        let spec = "
+            --name (string)
+            --count (integer default 1)
+            --lines (integer default 10)
+        ";
+        let mut config = HashMap::new();
+        config.insert("name".to_string(),"config".to_string());
+        config.insert("count".to_string(),"5".to_string());
+        config.insert("lines".to_string(),"20".to_string());
+
+        let mut aa = Args::new(spec);
+        assert!(aa.merge_defaults_from_hashmap(&config).unwrap().is_empty());
+        aa.try_parse_from(["--count","2"]).unwrap();
+        assert_eq!(aa.get_string("name"),"config");
+        assert_eq!(aa.get_integer("count"),2);
+        assert_eq!(aa.get_integer("lines"),20);
+
+        // after parsing, only flags not given are changed
+        let mut aa = parse_args(spec,&["--name","cli"]);
+        aa.merge_defaults_from_hashmap(&config).unwrap();
+        assert_eq!(aa.get_string("name"),"cli");
+        assert_eq!(aa.get_integer("count"),5);
+
+        config.insert("lines".to_string(),"many".to_string());
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.merge_defaults_from_hashmap(&config)),"flag 'lines': bad default: can't convert 'many' to integer - invalid digit found in string");
+
+        config.remove("lines");
+        config.insert("colour".to_string(),"red".to_string());
+        config.insert("size".to_string(),"large".to_string());
+        let mut aa = Args::new(spec);
+        assert_eq!(aa.merge_defaults_from_hashmap(&config).unwrap(),["colour","size"]);
+        aa.try_parse_from(empty_strings()).unwrap();
+        assert_eq!(aa.get_integer("count"),5);
+    }
+
+    #[test]
+    fn test_inline_array_min_length() {
+        let spec = "-p (integer... min-length 2)";
+        let aa = parse_args(spec,&["-p","10 20 30"]);
+        assert_eq!(aa.get_integers("p"),&[10,20,30]);
+        let aa = parse_args(spec,&[]);
+        assert_eq!(aa.get_integers("p"),&[] as &[i32]);
+
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        assert_eq!(err(aa.try_parse_from(["-p","10"])),
+            "flag 'p': expected at least 2 values in inline list, got 1");
+
+        // only for inline lists
+        let mut aa = Args::new("-p... (integer min-length 2)");
+        assert_eq!(err(aa.parse_spec()),"unknown modifier 'min-length': flag 'p'\nat line: '-p... (integer min-length 2)'");
+    }
+
+    #[test]
+    fn test_shell_escape() {
+        assert_eq!(shell_escape("hello"),"'hello'");
+        assert_eq!(shell_escape(""),"''");
+        assert_eq!(shell_escape("two words"),"'two words'");
+        assert_eq!(shell_escape("it's"),"'it'\\''s'");
+        assert_eq!(shell_escape("say \"hi\""),"'say \"hi\"'");
+        assert_eq!(shell_escape("$HOME"),"'$HOME'");
+        assert_eq!(shell_escape("`ls`"),"'`ls`'");
+        assert_eq!(shell_escape("a\0b"),"'ab'");
+
+        let aa = parse_args("--name (string default 'x')\n<file> (string)",&["--name","it's here","--","-x.txt"]);
+        assert_eq!(aa.to_shell_command(),"'--name' 'it'\\''s here' '--' '-x.txt'");
+    }
+
+    #[test]
+    fn test_unsigned_types() {
+        let spec = "
+            --port (u32 default 8080)
+            --size (u64)
+            --index (usize default 0)
+            --offsets... (usize)
+        ";
+        let aa = parse_args(spec,&["--size","8000000000","--offsets","1","--offsets","2"]);
+        assert_eq!(aa.get_u32("port"),8080);
+        assert_eq!(aa.get_u64("size"),8000000000);
+        assert_eq!(aa.get_usize("index"),0);
+        assert_eq!(aa.get_usizes("offsets"),&[1,2]);
+        assert_eq!(err(aa.get_integer_result("port")),"flag 'port': not a integer, but u32");
+
+        let aa = parse_args(spec,&["--port","-1","--size","18446744073709551616"]);
+        assert_eq!(err(aa.get_u32_result("port")),"flag 'port': can't convert '-1' to u32 - invalid digit found in string");
+        assert_eq!(err(aa.get_u64_result("size")),"flag 'size': can't convert '18446744073709551616' to u64 - number too large to fit in target type");
+
+        let mut aa = Args::new(spec);
+        aa.parse_spec().unwrap();
+        let getters: Vec<String> = aa.flags.iter().map(|f| f.getter_name()).collect();
+        assert_eq!(getters[0],"args.get_u32(\"port\")");
+        assert_eq!(getters[3],"args.get_usizes(\"offsets\")");
+        assert_eq!(aa.flags[1].vtype.rust_name(false),"u64");
+        assert_eq!(aa.flags[3].vtype.rust_name(true),"Vec<usize>");
+    }
+
+    #[test]
+    fn test_wide_types() {
+        let spec = "
+            --offset (integer64 default 0)
+            --limit (i64 0..5000000000 default 10)
+            --ratio (float64)
+            --scale (f64 0.0..1.0 default 0.5)
+            --times... (f64)
+        ";
+        let aa = parse_args(spec,&["--offset","4294967296","--ratio","0.1","--times","1.5","--times","2"]);
+        assert_eq!(aa.get_i64("offset"),4294967296);
+        assert_eq!(aa.get_i64("limit"),10);
+        assert_eq!(aa.get_f64("ratio"),0.1);
+        assert_eq!(aa.get_f64("scale"),0.5);
+        assert_eq!(aa.get_f64s("times"),&[1.5,2.0]);
+        assert_eq!(err(aa.get_float_result("ratio")),"flag 'ratio': not a float, but f64");
+
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.try_parse_from(["--limit","6000000000"])),"flag 'limit' out of range 0..5000000000");
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.try_parse_from(["--scale","1.5"])),"flag 'scale' out of range 0.0..1.0");
+        let mut aa = Args::new("--limit (i64 0..10 default 20)");
+        assert!(err(aa.parse_spec()).starts_with("flag 'limit': default 20 is outside declared range 0..10"));
+    }
+
+    #[test]
+    fn test_range_after_type() {
+        let spec = "
+            --count (integer 1..100 default 10)
+            --ratio (float 0..1 default 0.5)
+            --scale (float 0.0..1.0)
+        ";
+        let aa = parse_args(spec,&["--count","50","--scale","0.25"]);
+        assert_eq!(aa.get_integer("count"),50);
+        assert_eq!(aa.get_float("ratio"),0.5);
+        assert_eq!(aa.get_float("scale"),0.25);
+        assert_eq!(aa.describe_flag("ratio"),"--ratio  [float, optional, range 0..1, default: 0.5]\n");
+
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.try_parse_from(["--count","0"])),"flag 'count' out of range 1..100");
+        let mut aa = Args::new(spec);
+        assert_eq!(err(aa.try_parse_from(["--scale","1.5"])),"flag 'scale' out of range 0.0..1.0");
+        let mut aa = Args::new("--count (integer 1..10 default 20)");
+        assert!(err(aa.parse_spec()).starts_with("flag 'count': default 20 is outside declared range 1..10"));
+    }
+
+    #[test]
+    fn test_paths() {
+        let spec = "
+            --config (path default lapp.cfg)
+            -I, --include... (path)
+            <files> (path...)
+        ";
+        let aa = parse_args(spec,&["-I","lib","-I","src","a.txt","b.txt"]);
+        assert_eq!(aa.get_path("config"),PathBuf::from("lapp.cfg"));
+        assert_eq!(aa.get_paths("include"),[PathBuf::from("lib"),PathBuf::from("src")]);
+        assert_eq!(aa.get_paths("files"),[PathBuf::from("a.txt"),PathBuf::from("b.txt")]);
+        assert_eq!(err(aa.get_strings_result("include")),"flag 'include': wanted array of string, but is array of path");
+
+        let aa = parse_args(spec,&["x"]);
+        assert_eq!(aa.get_paths("include"),&[] as &[PathBuf]);
+        assert_eq!(aa.flags[0].getter_name(),"args.get_path(\"config\")");
+        assert_eq!(aa.flags[1].getter_name(),"args.get_paths(\"include\")");
+        assert_eq!(aa.flags[0].vtype.rust_name(false),"std::path::PathBuf");
+    }
+
+    #[test]
+    fn test_subcommand() {
+        let spec = "
+            -v, --verbose
+            <command> (string subcommand) one of 'commit' or 'push'
+        ";
+        let commit = "
+            -m, --message (string)
+            --amend
+        ";
+        let mut aa = Args::new(spec).with_program_name("tool");
+        aa.try_parse_from(["-v","commit","-m","fix","--amend"]).unwrap();
+        assert!(aa.get_bool("verbose"));
+        assert_eq!(aa.get_string("command"),"commit");
+        assert_eq!(aa.remaining_args(),["-m","fix","--amend"]);
+        let sub = aa.subcommand_args_result("commit",commit).unwrap();
+        assert_eq!(sub.get_string("message"),"fix");
+        assert!(sub.get_bool("amend"));
+        assert_eq!(sub.program_name(),"tool commit");
+
+        // flags after the subcommand belong to it
+        let aa = parse_args(spec,&["push","-v"]);
+        assert!(! aa.get_bool("verbose"));
+        assert_eq!(err(aa.subcommand_args_result("push","--force\n")),"no short flag 'v'");
+
+        let sub = Args::parse_subcommand(commit,&arg_strings(&["-m","x"])).unwrap();
+        assert_eq!(sub.get_string("message"),"x");
+
+        let mut aa = Args::new("<n> (integer subcommand)");
+        assert!(err(aa.parse_spec()).starts_with("unknown modifier 'subcommand'"));
+    }
+
+    #[test]
+    fn test_arg_spec() {
+        let specs = [
+            ArgSpec::long("verbose").short('v').boolean().help("enable verbose output"),
+            ArgSpec::long("output").short('o').default_str("stdout").help("output file"),
+            ArgSpec::long("level").type_name("1..5").default("2"),
+            ArgSpec::positional("files").type_name("string").multiple(),
+        ];
+        assert_eq!(specs[1].spec_line(),"  -o, --output (default 'stdout') output file");
+        assert_eq!(ArgSpec::long("scale").type_name("float").optional().spec_line(),"  --scale (float optional)");
+        assert_eq!(ArgSpec::positional("n").type_name("integer").multiple().default("1").spec_line(),"  <n> (integer... default 1)");
+        let mut aa = Args::from_specs(&specs);
+        aa.try_parse_from(["-v","a","b"]).unwrap();
+        assert!(aa.get_bool("verbose"));
+        assert_eq!(aa.get_string("output"),"stdout");
+        assert_eq!(aa.get_integer("level"),2);
+        assert_eq!(aa.get_strings("files"),["a","b"]);
+
+        // mixing with a spec string
+        let mut aa = Args::new("-k a flag\n");
+        aa.merge_spec(&ArgSpec::long("name").short('n').type_name("string")).unwrap();
+        assert_eq!(err(aa.merge_spec(&ArgSpec::long("key").short('k'))),"short flag 'k' already defined: flag 'key'");
+        aa.try_parse_from(["-k","-n","joe"]).unwrap();
+        assert!(aa.get_bool("k"));
+        assert_eq!(aa.get_string("name"),"joe");
+    }
+
+    #[test]
+    fn test_iter_multiple() {
+        let aa = parse_args(SIMPLE,&["-I.","-I..","-p","10 20","in","one","two"]);
+        let inc: Vec<&str> = aa.iter_multiple_strings("include").collect();
+        assert_eq!(inc,[".",".."]);
+        assert_eq!(aa.iter_multiple_integers("p").sum::<i32>(),30);
+        assert_eq!(aa.iter_multiple_strings("out").count(),2);
+        let vals: Vec<String> = aa.iter_multiple("out").map(|v| v.as_string().unwrap()).collect();
+        assert_eq!(vals,["one","two"]);
+    }
+
+    const POINT: &str = "
+        --point... (float exactly 2)
+        --tag... (string max 2)
+    ";
+
+    #[test]
+    fn test_exact_count() {
+        let aa = parse_args(POINT,&["--point","1","--point","2.5","--tag","a"]);
+        assert_eq!(aa.get_floats("point"),[1.0,2.5]);
+        assert_eq!(aa.get_strings("tag"),["a"]);
+
+        let mut args = Args::new(POINT);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(["--point","1","--point","2","--point","3"])),
+            "flag 'point': requires exactly 2 values, got 3");
+        let mut args = Args::new(POINT);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(["--point","1"])),
+            "flag 'point': requires exactly 2 values, got 1");
+        let mut args = Args::new(POINT);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(["--point","1","--point","2","--tag","a","--tag","b","--tag","c"])),
+            "flag 'tag': requires at most 2 values, got 3");
+
+        let mut args = Args::new("--point (float exactly 2)");
+        assert!(err(args.parse_spec()).starts_with("only multiple flags can have a count"));
+        let mut args = Args::new("--point... (float exactly two)");
+        assert!(err(args.parse_spec()).starts_with("'exactly' needs a count"));
+    }
+
+    #[test]
+    fn test_merge_values() {
+        let merged = Value::Int(1).merge(&Value::Int(2)).unwrap();
+        assert_eq!(merged.as_int().unwrap(), 1);
+        let merged = Value::Bool(false).merge(&Value::Bool(true)).unwrap();
+        assert!(merged.as_bool().unwrap());
+        let a = Type::Arr(Box::new(Type::Int)).parse_string("1 2").unwrap();
+        let b = Type::Arr(Box::new(Type::Int)).parse_string("3").unwrap();
+        let merged = a.merge(&b).unwrap();
+        let ints: Vec<i32> = merged.as_array().unwrap().iter().map(|v| v.as_int().unwrap()).collect();
+        assert_eq!(ints, [1,2,3]);
+        assert!(a.merge(&Value::empty_array()).is_ok());
+        assert_eq!(err(Value::Int(1).merge(&Value::Str("x".into()))), "cannot merge integer with string");
+        assert_eq!(err(a.merge(&Value::Int(1))), "cannot merge array with non-array value");
+    }
+
+    #[cfg(feature = "url-type")]
+    #[test]
+    fn test_url() {
+        let spec = "
+            --endpoint (url)
+            --secure (url https default 'https://example.com')
+        ";
+        let aa = parse_args(spec,&["--endpoint","http://api.example.com/v1"]);
+        assert_eq!(aa.get_url("endpoint").host_str(), Some("api.example.com"));
+        assert_eq!(aa.get_url("secure").scheme(), "https");
+
+        let aa = parse_args(spec,&["--endpoint","not a url"]);
+        assert_eq!(err(aa.get_url_result("endpoint")),
+            "flag 'endpoint': 'not a url' is not a valid URL: relative URL without a base");
+
+        let mut args = Args::new(spec);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(["--endpoint","x:y","--secure","http://example.com"])),
+            "flag 'secure': 'http://example.com/' is not an https URL");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex() {
+        let spec = "
+            --filter (regex)
+            --skip (regex default '^#')
+        ";
+        let aa = parse_args(spec,&["--filter","^\\d+$"]);
+        assert!(aa.get_regex("filter").is_match("42"));
+        assert!(! aa.get_regex("filter").is_match("x42"));
+        assert!(aa.get_regex("skip").is_match("# comment"));
+
+        let aa = parse_args(spec,&["--filter","(ab"]);
+        assert!(err(aa.get_regex_result("filter")).starts_with("flag 'filter': '(ab' is not a valid regex"));
+
+        let mut args = Args::new(spec);
+        let decls = args.declarations("Args");
+        // the field type is a full path, so no import is needed
+        assert!(decls.contains("\tfilter: regex::Regex,"));
+        assert!(! decls.contains("use regex"));
+    }
+
+    #[test]
+    fn test_raw_rest() {
+        let spec = "
+            -v, --verbose
+            <prog> (string)
+            <rest> (raw)
+        ";
+        let aa = parse_args(spec,&["-v","run","-x","--flag","'1 2'"]);
+        assert!(aa.get_bool("verbose"));
+        assert_eq!(aa.get_string("prog"),"run");
+        assert_eq!(aa.get_strings("rest"),["-x","--flag","'1 2'"]);
+
+        let aa = parse_args(spec,&["run"]);
+        assert_eq!(aa.get_strings("rest"),empty_strings());
+
+        // the only positional argument may be raw
+        let aa = parse_args("<rest> (raw)",&["-x","y","--z"]);
+        assert_eq!(aa.get_strings("rest"),["-x","y","--z"]);
+
+        let mut args = Args::new("--rest (raw)");
+        assert!(err(args.parse_spec()).starts_with("raw type only allowed for positional arguments"));
+    }
+
+    #[test]
+    fn test_permissive() {
+        let spec = "
+            -v, --verbose
+            <in> (string)
+        ";
+        let mut args = Args::new(spec);
+        args.set_permissive(true);
+        args.parse_spec().expect("spec failed");
+        args.try_parse_from(["--plugin=x","-vq","in","extra","--more"]).expect("scan failed");
+        assert!(args.get_bool("verbose"));
+        assert_eq!(args.unrecognized_flags(),["--plugin=x","-q","--more"]);
+        assert_eq!(args.remaining_args(),["extra"]);
+
+        let mut args = Args::new(spec);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(["--plugin","in"])),"no long flag 'plugin'");
+    }
+
+    #[test]
+    fn test_check_unknown_flags() {
+        let spec = "
+            -v, --verbose
+            -n (integer default 1)
+            <in> (string)
+        ";
+        let mut args = Args::new(spec).check_unknown_flags(false);
+        args.parse_spec().expect("spec failed");
+        args.try_parse_from(["--plugin","-vqn5","in"]).expect("scan failed");
+        assert!(args.get_bool("verbose"));
+        assert_eq!(args.get_integer("n"),5);
+        assert_eq!(args.unrecognized_flags(),["--plugin","-q"]);
+        // extra arguments are collected as well
+        args.clear();
+        args.try_parse_from(["in","extra"]).expect("scan failed");
+        assert_eq!(args.remaining_args(),["extra"]);
+
+        let mut args = Args::new(spec).check_unknown_flags(true);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(err(args.try_parse_from(["-vq","in"])),"no short flag 'q'");
+    }
+
+    #[test]
+    fn test_required_flags() {
+        let mut args = Args::new("
+            -v, --verbose
+            --scale (default 1.0)
+            --name (string)
+            <file> (string)
+        ");
+        args.parse_spec().expect("spec failed");
+        assert_eq!(args.required_flags(),["file","name"]);
+        assert_eq!(args.optional_flags(),["help","scale","verbose"]);
+    }
+
+    #[cfg(feature = "toml-spec")]
+    #[test]
+    fn test_toml_spec() {
+        let toml = r#"
+            description = "Testing Lapp"
+            [flags.verbose]
+            short = "v"
+            help = "verbose output"
+            [flags.output]
+            type = "string"
+            default = "out.txt"
+            [flags.scale]
+            default = 1.0
+            [flags.include]
+            short = "I"
+            type = "string"
+            multiple = true
+            [args.files]
+            type = "string"
+            multiple = true
+        "#;
+        let spec = "
+            Testing Lapp
+            -v, --verbose verbose output
+            --output (string default 'out.txt')
+            --scale (default 1.0)
+            -I, --include... (string)
+            <files> (string...)
+        ";
+        let targs = Args::from_toml(toml).expect("toml spec failed");
+        let mut args = Args::new(spec);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(targs.flags.len(), args.flags.len());
+        for (t, f) in targs.flags.iter().zip(args.flags.iter()) {
+            assert_eq!(t.long, f.long);
+            assert_eq!(t.short, f.short);
+            assert_eq!(t.vtype, f.vtype);
+            assert_eq!(t.is_multiple, f.is_multiple);
+            assert_eq!(t.pos, f.pos);
+            assert_eq!(t.help, f.help);
+            assert_eq!(format!("{:?}",t.defval), format!("{:?}",f.defval));
+        }
+        assert!(err(Args::from_toml("[flags.x]\nkind = 'string'")).starts_with("'x': unknown key 'kind'"));
+    }
+
+    #[test]
+    fn test_dash_files() {
+        let aa = parse_args("--in (infile)\n--out (outfile)",&["--in","-","--out","-"]);
+        assert!(aa.get_infile_result("in").is_ok());
+        assert!(aa.get_outfile_result("out").is_ok());
+        let aa = parse_args("--in (infile)",&["--in","no-such-file"]);
+        assert_eq!(err(aa.get_infile_result("in")),
+            "flag 'in': can't open 'no-such-file' for reading: No such file or directory (os error 2)");
+    }
+
+    #[test]
+    fn test_buffered_files() {
+        use std::io::BufRead;
+        let aa = parse_args("--in (infile)\n--out (outfile)",&["--in","Cargo.toml","--out","-"]);
+        let first = aa.get_buffered_infile("in").lines().next().unwrap().unwrap();
+        assert_eq!(first,"[package]");
+        assert!(aa.get_buffered_outfile_result("out").is_ok());
+        let aa = parse_args("--in (infile)",&["--in","no-such-file"]);
+        assert!(aa.get_buffered_infile_result("in").is_err());
+    }
+
+    #[test]
+    fn test_flag_types() {
+        let mut args = Args::new(SIMPLE);
+        args.parse_spec().expect("spec failed");
+        assert_eq!(*args.get_flag_type("output").unwrap(), Type::Str);
+        assert_eq!(*args.get_flag_type("p").unwrap(), Type::Arr(Box::new(Type::Int)));
+        assert!(args.is_flag_multiple("include").unwrap());
+        assert!(! args.is_flag_multiple("verbose").unwrap());
+        assert_eq!(err(args.get_flag_type("nope")), "no long flag 'nope'");
+
+        let info: Vec<_> = args.flags_iter().collect();
+        assert_eq!(info[0].name, "verbose");
+        assert_eq!(info[0].short, Some('v'));
+        assert_eq!(info[0].help, "verbose flag");
+        let out = args.flags_iter().find(|f| f.name == "out").unwrap();
+        assert_eq!(out.position, Some(2));
+        assert!(out.is_multiple);
+        assert_eq!(*out.vtype, Type::Str);
+    }
+
+    #[test]
+    fn test_quoted_array_flag() {
+        let aa = parse_args("--names (string...)",&["--names","'Frodo Baggins' Sam"]);
+        assert_eq!(aa.get_strings("names"),["Frodo Baggins","Sam"]);
+    }
+
 
 }
@@ -0,0 +1,119 @@
+// Declaring flags with Rust-like syntax, rather than a spec string
+
+/// build a spec string from Rust-like flag declarations.
+/// The defaults must match the declared Rust type, so `verbose: i32 = false`
+/// is a compile error.
+///
+/// ```
+/// #[macro_use] extern crate lapp;
+/// # fn main() {
+/// let spec = lapp_spec! {
+///     verbose: bool,
+///     output: String = "stdout",
+///     count: i32 required,
+///     scale: f32 = 1.0,
+///     include: Vec<String>,
+/// };
+/// assert_eq!(spec, "\
+/// --verbose
+/// --output (string default 'stdout')
+/// --count (integer)
+/// --scale (float default 1.0)
+/// --include... (string)
+/// ");
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use] extern crate lapp;
+/// # fn main() {
+/// let spec = lapp_spec! { verbose: i32 = false };
+/// # }
+/// ```
+#[macro_export]
+macro_rules! lapp_spec {
+    (@acc [$($s:expr),*] {$($c:tt)*}) => {
+        {
+            $($c)*
+            concat!($($s),*)
+        }
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s),*] {$($c)*} $($rest)*)
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} $name:ident : bool = $def:literal , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s,)* "--", stringify!($name), " (bool default ", $def, ")\n"]
+            {$($c)* let _: bool = $def;} $($rest)*)
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} $name:ident : bool , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s,)* "--", stringify!($name), "\n"] {$($c)*} $($rest)*)
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} $name:ident : String = $def:literal , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s,)* "--", stringify!($name), " (string default '", $def, "')\n"]
+            {$($c)* let _: &str = $def;} $($rest)*)
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} $name:ident : $t:ident = $def:literal , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s,)* "--", stringify!($name), " (", $crate::lapp_spec!(@type $t), " default ", $def, ")\n"]
+            {$($c)* let _: $t = $def;} $($rest)*)
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} $name:ident : $t:ident required , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s,)* "--", stringify!($name), " (", $crate::lapp_spec!(@type $t), ")\n"]
+            {$($c)*} $($rest)*)
+    };
+    (@acc [$($s:expr),*] {$($c:tt)*} $name:ident : Vec<$t:ident> , $($rest:tt)*) => {
+        $crate::lapp_spec!(@acc [$($s,)* "--", stringify!($name), "... (", $crate::lapp_spec!(@type $t), ")\n"]
+            {$($c)*} $($rest)*)
+    };
+    (@type String) => { "string" };
+    (@type i32) => { "integer" };
+    (@type u32) => { "u32" };
+    (@type u64) => { "u64" };
+    (@type usize) => { "usize" };
+    (@type i64) => { "i64" };
+    (@type f64) => { "f64" };
+    (@type f32) => { "float" };
+    (@type bool) => { "bool" };
+    ($($decl:tt)*) => {
+        $crate::lapp_spec!(@acc [""] {} $($decl)* ,)
+    };
+}
+
+/// declare flags with Rust-like syntax (see `lapp_spec!`) and parse
+/// the command-line, quitting on error.
+///
+/// ```no_run
+/// #[macro_use] extern crate lapp;
+/// # fn main() {
+/// let args = args! {
+///     verbose: bool,
+///     count: i32 = 10,
+/// };
+/// let count = args.get_integer("count");
+/// # }
+/// ```
+///
+/// The macros can also be called by path, without `#[macro_use]`:
+///
+/// ```
+/// extern crate lapp;
+/// # fn main() {
+/// let spec = lapp::lapp_spec! { verbose: bool, count: i32 = 10 };
+/// assert_eq!(spec, "--verbose\n--count (integer default 10)\n");
+/// let mut args = lapp::Args::new(spec);
+/// args.try_parse_from(["--count", "2"]).unwrap();
+/// assert_eq!(args.get_integer("count"), 2);
+/// # }
+/// ```
+///
+/// ```no_run
+/// extern crate lapp;
+/// # fn main() {
+/// let args = lapp::args! { verbose: bool };
+/// # }
+/// ```
+#[macro_export]
+macro_rules! args {
+    ($($decl:tt)*) => {
+        $crate::Args::new($crate::lapp_spec!($($decl)*)).parse_owned()
+    };
+}
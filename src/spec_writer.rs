@@ -0,0 +1,79 @@
+// Building a spec with write! and writeln!
+
+use std::fmt;
+use std::borrow::Cow;
+use super::Args;
+use types::*;
+
+/// accumulates spec lines, checking each line as it is completed.
+///
+/// ```
+/// use std::fmt::Write;
+/// let mut spec = lapp::SpecWriter::new();
+/// writeln!(spec, "  -v, --verbose verbose flag").unwrap();
+/// writeln!(spec, "  --count (1..100)").unwrap();
+/// let mut args = spec.into_args();
+/// args.parse_spec().unwrap();
+/// args.parse_command_line(vec!["--count".into(), "10".into()]).unwrap();
+/// assert_eq!(args.get_integer("count"), 10);
+/// ```
+pub struct SpecWriter {
+    text: String,
+    // start of the line still being written
+    pending: usize,
+    checker: Args<'static>,
+    error: Option<LappError>,
+}
+
+impl SpecWriter {
+    pub fn new() -> SpecWriter {
+        SpecWriter {
+            text: String::new(),
+            pending: 0,
+            checker: Args::new(""),
+            error: None,
+        }
+    }
+
+    /// the first bad spec line, if any
+    pub fn error(&self) -> Option<&LappError> {
+        self.error.as_ref()
+    }
+
+    /// the spec so far
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// an Args ready to be parsed
+    pub fn into_args(self) -> Args<'static> {
+        let mut args = Args::new("");
+        args.text = Cow::Owned(self.text);
+        args
+    }
+}
+
+impl Default for SpecWriter {
+    fn default() -> SpecWriter {
+        SpecWriter::new()
+    }
+}
+
+impl fmt::Write for SpecWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.error.is_some() {
+            return Err(fmt::Error);
+        }
+        self.text.push_str(s);
+        while let Some(idx) = self.text[self.pending..].find('\n') {
+            let end = self.pending + idx;
+            let line = self.text[self.pending..end].to_string();
+            self.pending = end + 1;
+            if let Err(e) = self.checker.parse_spec_line(&line) {
+                self.error = Some(LappError(format!("{}\nat line: '{}'",e,line)));
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
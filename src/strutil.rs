@@ -30,14 +30,41 @@ pub fn ends_with(pslice: &mut &str, start: &str) -> bool {
     }
 }
 
+// show where we were looking, e.g. "cannot find ')' in 'integer 1..10'"
+fn not_found(sub: &str, slice: &str) -> LappError {
+    let start: String = slice.chars().take(20).collect();
+    LappError(format!("cannot find '{}' in '{}'",sub,start))
+}
+
+// take everything up to `sub`, trimmed, and move past it.
+// If `sub` comes first, the result is empty (e.g. '<>')
 pub fn grab_upto(pslice: &mut &str, sub: &str) -> Result<String,LappError> {
     if let Some(idx) = pslice.find(sub) {
         let s = (&pslice[0..idx].trim()).to_string();
         *pslice = &pslice[idx+sub.len()..];
         Ok(s)
     } else {
-        Err(LappError(format!("cannot find end {:?}",sub)))
+        Err(not_found(sub,pslice))
+    }
+}
+
+// like grab_upto, but the end char does not count inside quotes,
+// so that e.g. "(default 'a)b')" works
+pub fn grab_upto_unquoted(pslice: &mut &str, end: char) -> Result<String,LappError> {
+    let mut quote = None;
+    for (idx, ch) in pslice.char_indices() {
+        match quote {
+            Some(q) => if ch == q { quote = None; },
+            None => if ch == '\'' || ch == '"' {
+                quote = Some(ch);
+            } else if ch == end {
+                let s = pslice[0..idx].trim().to_string();
+                *pslice = &pslice[idx+end.len_utf8()..];
+                return Ok(s);
+            }
+        }
     }
+    Err(not_found(&end.to_string(),pslice))
 }
 
 pub fn split_with<'a>(slice: &'a str, needle: &str) -> Option<(&'a str,&'a str)> {
@@ -50,23 +77,121 @@ pub fn split_with<'a>(slice: &'a str, needle: &str) -> Option<(&'a str,&'a str)>
     }
 }
 
+// split into words, shell-style. Single quotes prevent any escaping,
+// double quotes allow backslash escapes, and a '#' at the start of
+// a word comments out the rest of the line.
+pub fn tokenize(s: &str) -> Result<Vec<String>,LappError> {
+    fn unclosed(quote: char) -> Result<Vec<String>,LappError> {
+        Err(LappError(format!("unclosed quote {:?}",quote)))
+    }
+    let mut res = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return unclosed(ch)
+                    }
+                }
+            },
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') | Some(c @ '$') | Some(c @ '`') => word.push(c),
+                            Some('\n') => {},
+                            Some(c) => { word.push('\\'); word.push(c); },
+                            None => return unclosed(ch)
+                        },
+                        Some(c) => word.push(c),
+                        None => return unclosed(ch)
+                    }
+                }
+            },
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some('\n') => {},
+                    Some(c) => word.push(c),
+                    None => word.push(ch)
+                }
+            },
+            '#' if ! in_word => {
+                // comment runs to end of line
+                for c in chars.by_ref() {
+                    if c == '\n' { break; }
+                }
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    res.push(word);
+                    word = String::new();
+                    in_word = false;
+                }
+            },
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        res.push(word);
+    }
+    Ok(res)
+}
+
+// strip at most `indent` leading whitespace characters
+fn strip_indent(line: &str, indent: usize) -> &str {
+    let idx = line.char_indices()
+        .take(indent)
+        .take_while(|&(_,c)| c.is_whitespace())
+        .last().map(|(i,c)| i + c.len_utf8())
+        .unwrap_or(0);
+    &line[idx..]
+}
+
 pub fn dedent(s: &str) -> String {
     let mut lines = s.lines();
     let mut res = String::new();
-    let mut idx = None;
+    let mut indent = None;
     while let Some(line) = lines.next() {
-        if let Some(pos) = line.chars().position(|c| ! c.is_whitespace()) {
-            idx = Some(pos);
-            res += &line[pos..];
+        if let Some(idx) = line.find(|c: char| ! c.is_whitespace()) {
+            indent = Some(line[..idx].chars().count());
+            res += &line[idx..];
             res.push('\n');
             break;
         }
     }
-    if let Some(pos) = idx {
+    if let Some(indent) = indent {
         while let Some(line) = lines.next() {
-            res += if line.len() >= pos { &line[pos..] } else { line };
+            res += strip_indent(line,indent);
             res.push('\n');
         }
     }
     res
 }
+
+/// quote a value for a POSIX shell. The value is wrapped in single quotes,
+/// and each single quote becomes `'\''`, so nothing inside is special.
+/// A shell cannot pass on a null byte, so these are dropped.
+pub fn shell_escape(s: &str) -> String {
+    let mut res = String::from("'");
+    for ch in s.chars() {
+        match ch {
+            '\'' => res += "'\\''",
+            '\0' => {},
+            c => res.push(c)
+        }
+    }
+    res.push('\'');
+    res
+}
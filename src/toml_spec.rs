@@ -0,0 +1,107 @@
+// Converting a TOML table into a lapp spec.
+// Each [flags.NAME] table becomes a flag line, and each [args.NAME] table
+// becomes a positional argument, in the order they appear:
+//
+//   description = "a test program"
+//   [flags.verbose]
+//   short = "v"
+//   help = "verbose output"
+//   [flags.output]
+//   type = "string"
+//   default = "out.txt"
+
+use toml::{Table,Value as TomlValue};
+use super::types::*;
+use super::arg_spec::ArgSpec;
+
+fn default_string(name: &str, v: &TomlValue) -> Result<String> {
+    match *v {
+        TomlValue::String(ref s) => Ok(format!("'{}'",s)),
+        TomlValue::Integer(n) => Ok(n.to_string()),
+        // keep the point so the default is still deduced as float
+        TomlValue::Float(x) => Ok(format!("{:?}",x)),
+        TomlValue::Boolean(b) => Ok(b.to_string()),
+        _ => lapp_error(format!("'{}': default must be a string, number or boolean",name))
+    }
+}
+
+fn string_field<'a>(name: &str, table: &'a Table, key: &str) -> Result<Option<&'a str>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(TomlValue::String(s)) => Ok(Some(s)),
+        Some(_) => lapp_error(format!("'{}': '{}' must be a string",name,key))
+    }
+}
+
+fn spec_line(name: &str, v: &TomlValue, positional: bool) -> Result<String> {
+    let table = match *v {
+        TomlValue::Table(ref t) => t,
+        _ => return lapp_error(format!("'{}' must be a table",name))
+    };
+    for key in table.keys() {
+        match key.as_str() {
+            "short" | "type" | "default" | "help" | "multiple" => {},
+            _ => return lapp_error(format!("'{}': unknown key '{}'",name,key))
+        }
+    }
+    let multiple = match table.get("multiple") {
+        None => false,
+        Some(&TomlValue::Boolean(b)) => b,
+        Some(_) => return lapp_error(format!("'{}': 'multiple' must be a boolean",name))
+    };
+    let mut spec = if positional { ArgSpec::positional(name) } else { ArgSpec::long(name) };
+    if let Some(short) = string_field(name,table,"short")? {
+        let mut chars = short.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => spec = spec.short(ch),
+            _ => return lapp_error(format!("'{}': 'short' must be a single character",name))
+        }
+    }
+    if multiple {
+        spec = spec.multiple();
+    }
+    let vtype = string_field(name,table,"type")?;
+    let defval = match table.get("default") {
+        Some(v) => Some(default_string(name,v)?),
+        None => None
+    };
+    if positional && vtype.is_none() && defval.is_none() {
+        return lapp_error(format!("'{}': argument needs a type",name));
+    }
+    if let Some(t) = vtype {
+        spec = spec.type_name(t);
+    }
+    if let Some(d) = defval {
+        spec = spec.default(&d);
+    }
+    if let Some(help) = string_field(name,table,"help")? {
+        spec = spec.help(help);
+    }
+    let mut line = spec.spec_line();
+    line.push('\n');
+    Ok(line)
+}
+
+// generate the equivalent spec text for a TOML spec
+pub fn spec_from_toml(s: &str) -> Result<String> {
+    let table: Table = match s.parse() {
+        Ok(t) => t,
+        Err(e) => return lapp_error(format!("bad TOML spec: {}",e))
+    };
+    let mut res = String::new();
+    for (key, v) in &table {
+        match (key.as_str(), v) {
+            ("description", TomlValue::String(s)) => {
+                res += s;
+                res.push('\n');
+            },
+            ("flags", TomlValue::Table(flags)) | ("args", TomlValue::Table(flags)) => {
+                for (name, v) in flags {
+                    res += &spec_line(name,v,key == "args")?;
+                }
+            },
+            _ => return lapp_error(format!("unexpected '{}' in TOML spec",key))
+        }
+    }
+    Ok(res)
+}
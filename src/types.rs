@@ -9,6 +9,7 @@ use std::env;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::PathBuf;
+use super::strutil::tokenize;
 
 #[derive(Debug)]
 pub struct LappError(pub String);
@@ -19,29 +20,95 @@ impl fmt::Display for LappError {
     }
 }
 
-impl Error for LappError {
-    fn description(&self) -> &str {
-        &self.0
-    }
-}
+// Display gives the message; there is no underlying cause
+impl Error for LappError {}
 
 pub type Result<T> = result::Result<T,LappError>;
 
-pub fn error<T, M: string::ToString>(msg: M) -> Result<T> {
+pub fn lapp_error<T, M: string::ToString>(msg: M) -> Result<T> {
     Err(LappError(msg.to_string()))
 }
 
 
+// not the full RFC 5322 - just something@domain.tld without spaces
+fn looks_like_email(s: &str) -> bool {
+    if s.chars().any(|c| c.is_whitespace()) {
+        return false;
+    }
+    let mut parts = s.split('@');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(local), Some(domain), None) => ! local.is_empty() &&
+            domain.rfind('.').is_some_and(|idx| idx > 0 && idx + 1 < domain.len()),
+        _ => false
+    }
+}
+
+// expand '${VAR}' and '${VAR:-fallback}' using the environment
+fn expand_env_vars(val: &str) -> Result<String> {
+    let mut res = String::new();
+    let mut rest = val;
+    while let Some(idx) = rest.find("${") {
+        res += &rest[0..idx];
+        rest = &rest[idx+2..];
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => return lapp_error(format!("unterminated '${{' in default '{}'",val))
+        };
+        let inner = &rest[0..end];
+        rest = &rest[end+1..];
+        let (name, fallback) = match inner.find(":-") {
+            Some(i) => (&inner[0..i], Some(&inner[i+2..])),
+            None => (inner, None)
+        };
+        if name.is_empty() || ! name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            || fallback.is_some_and(|f| f.contains('$')) {
+            return lapp_error(format!("bad variable '${{{}}}' in default '{}'",inner,val));
+        }
+        match (env::var(name), fallback) {
+            (Ok(v), _) => res += &v,
+            (Err(_), Some(f)) => res += f,
+            (Err(_), None) => return lapp_error(format!("environment variable '{}' is not set",name))
+        }
+    }
+    res += rest;
+    Ok(res)
+}
+
 // the flag types
 #[derive(Debug, PartialEq)]
 pub enum Type {
     Str,
     Int,
+    // unsigned and wider integers
+    U32,
+    U64,
+    USize,
+    I64,
     Float,
+    F64,
+    // integer or float, always stored as f64
+    Number,
+    // 'key=value', always a multiple flag
+    KeyVal,
     Bool,
+    // a bool given as a value, like 'yes' or 'off'
+    BoolString,
     FileIn,
+    // an input file read up front, as an array of lines
+    FileInLines,
     FileOut,
     Path,
+    Raw,
+    // a string that looks like an email address
+    Email,
+    // a string which is asked for at the terminal, rather than given
+    Password,
+    Hex,
+    Radix(u32),
+    #[cfg(feature = "url-type")]
+    Url,
+    #[cfg(feature = "regex")]
+    Regex,
     None,
     Arr(Box<Type>),
     Error,
@@ -57,12 +124,29 @@ impl Type {
         match s {
         "string" => Ok(Type::Str),
         "integer" => Ok(Type::Int),
+        "u32" => Ok(Type::U32),
+        "u64" => Ok(Type::U64),
+        "usize" => Ok(Type::USize),
+        "integer64" | "i64" => Ok(Type::I64),
+        "float64" | "f64" => Ok(Type::F64),
         "float" => Ok(Type::Float),
+        "number" => Ok(Type::Number),
+        "keyval" => Ok(Type::KeyVal),
         "bool" => Ok(Type::Bool),
+        "bool-string" => Ok(Type::BoolString),
         "infile" => Ok(Type::FileIn),
+        "infile-lines" => Ok(Type::FileInLines),
         "outfile" => Ok(Type::FileOut),
         "path" => Ok(Type::Path),
-        _ => error(format!("not a known type {}",s))
+        "raw" => Ok(Type::Raw),
+        "email" => Ok(Type::Email),
+        "password" => Ok(Type::Password),
+        "hex" => Ok(Type::Hex),
+        #[cfg(feature = "url-type")]
+        "url" => Ok(Type::Url),
+        #[cfg(feature = "regex")]
+        "regex" => Ok(Type::Regex),
+        _ => lapp_error(format!("not a known type {}",s))
         }
     }
 
@@ -78,25 +162,56 @@ impl Type {
     pub fn short_name(&self) -> String {
         let s;
         (match *self {
-         Type::Str => "string",
-         Type::Int => "integer",
+         Type::Str | Type::Raw | Type::Email | Type::Password => "string",
+         Type::Int | Type::Hex | Type::Radix(_) => "integer",
+         Type::U32 => "u32",
+         Type::U64 => "u64",
+         Type::USize => "usize",
+         Type::I64 => "i64",
+         Type::F64 => "f64",
          Type::Float => "float",
-         Type::Bool => "bool",
+         Type::Number => "number",
+         Type::KeyVal => "keyval",
+         Type::Bool | Type::BoolString => "bool",
          Type::FileIn => "infile",
+         Type::FileInLines => "infile-lines",
          Type::FileOut => "outfile",
+         Type::Path => "path",
+         #[cfg(feature = "url-type")]
+         Type::Url => "url",
+         #[cfg(feature = "regex")]
+         Type::Regex => "regex",
          Type::Arr(ref t) => { s=format!("array of {}",t.short_name()); s.as_str() }
+         // e.g. the elements of an empty array
+         Type::None => "unknown",
          _ => "bad"
         }).to_string()
     }
 
     pub fn rust_name(&self, multiple: bool) -> String {
+        // all the values of a keyval flag make up one map
+        if *self == Type::KeyVal {
+            return "HashMap<String,String>".into();
+        }
         let mut res = match *self {
-            Type::Bool => "bool".into(),
+            Type::Bool | Type::BoolString => "bool".into(),
             Type::Float => "f32".into(),
-            Type::Int => "i32".into(),
-            Type::Str => "String".into(),
+            Type::Number => "f64".into(),
+            Type::Int | Type::Hex | Type::Radix(_) => "i32".into(),
+            Type::U32 => "u32".into(),
+            Type::U64 => "u64".into(),
+            Type::USize => "usize".into(),
+            Type::I64 => "i64".into(),
+            Type::F64 => "f64".into(),
+            Type::Str | Type::Raw | Type::Email | Type::Password => "String".into(),
             Type::FileIn => "Box<Read>".into(),
+            Type::FileInLines => "Vec<String>".into(),
             Type::FileOut => "Box<Write>".into(),
+            Type::Path => "std::path::PathBuf".into(),
+            #[cfg(feature = "url-type")]
+            Type::Url => "url::Url".into(),
+            #[cfg(feature = "regex")]
+            Type::Regex => "regex::Regex".into(),
             Type::Arr(ref t) => format!("Vec<{}>",t.rust_name(false)),
             _ => "bad".into()
         };
@@ -108,36 +223,133 @@ impl Type {
 
     pub fn parse_string(&self, s: &str) -> Result<Value> {
         match *self {
-        Type::Str => Ok(Value::Str(s.to_string())),
+        Type::Str | Type::Raw | Type::Password => Ok(Value::Str(s.to_string())),
         Type::Int =>
             match s.parse::<i32>() {
                 Ok(n) => Ok(Value::Int(n)),
-                Err(e) => Ok(Value::Error(format!("can't convert '{}' to integer - {}",s,e.description())))
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to integer - {}",s,e)))
+            },
+        Type::U32 =>
+            match s.parse::<u32>() {
+                Ok(n) => Ok(Value::U32(n)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to u32 - {}",s,e)))
+            },
+        Type::U64 =>
+            match s.parse::<u64>() {
+                Ok(n) => Ok(Value::U64(n)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to u64 - {}",s,e)))
+            },
+        Type::USize =>
+            match s.parse::<usize>() {
+                Ok(n) => Ok(Value::USize(n)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to usize - {}",s,e)))
+            },
+        Type::I64 =>
+            match s.parse::<i64>() {
+                Ok(n) => Ok(Value::I64(n)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to i64 - {}",s,e)))
+            },
+        Type::F64 =>
+            match s.parse::<f64>() {
+                Ok(x) => Ok(Value::F64(x)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to f64 - {}",s,e)))
             },
+        Type::Hex => {
+            let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+            match i32::from_str_radix(digits,16) {
+                Ok(n) => Ok(Value::Int(n)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to hex integer - {}",s,e)))
+            }
+        },
+        Type::Radix(base) => {
+            let digits = match base {
+                2 => s.trim_start_matches("0b"),
+                8 => s.trim_start_matches("0o"),
+                _ => s
+            };
+            match i32::from_str_radix(digits,base) {
+                Ok(n) => Ok(Value::Int(n)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to base {} integer - {}",s,base,e)))
+            }
+        },
         Type::Float =>
             match s.parse::<f32>() {
                 Ok(v) => Ok(Value::Float(v)),
-                Err(e) => Ok(Value::Error(format!("can't convert '{}' to float - {}",s,e.description())))
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to float - {}",s,e)))
+            },
+        // integers are read as floats, keeping full f64 precision
+        Type::Number =>
+            match s.parse::<f64>() {
+                Ok(x) => Ok(Value::F64(x)),
+                Err(_) => Ok(Value::Error(format!("can't convert '{}' to number",s)))
+            },
+        Type::Bool =>
+            match s.parse::<bool>() {
+                Ok(b) => Ok(Value::Bool(b)),
+                Err(e) => Ok(Value::Error(format!("can't convert '{}' to bool - {}",s,e)))
+            },
+        Type::Email =>
+            if looks_like_email(s) {
+                Ok(Value::Str(s.to_string()))
+            } else {
+                Ok(Value::Error(format!("'{}' does not look like a valid email address",s)))
+            },
+        Type::BoolString =>
+            match s.to_lowercase().as_str() {
+                "true" | "yes" | "y" | "on" | "1" => Ok(Value::Bool(true)),
+                "false" | "no" | "n" | "off" | "0" => Ok(Value::Bool(false)),
+                _ => Ok(Value::Error(format!("can't convert '{}' to bool - expecting yes/no, on/off, true/false or 1/0",s)))
             },
         Type::FileIn => Ok(Value::FileIn(s.to_string())),
+        // note: the whole file is read into memory!
+        Type::FileInLines => {
+            let mut text = String::new();
+            let res = Value::FileIn(s.to_string()).as_infile()
+                .and_then(|mut f| f.read_to_string(&mut text)
+                    .or_else(|e| lapp_error(format!("can't read '{}': {}",s,e))));
+            match res {
+                Ok(_) => Ok(Value::Arr(text.lines().map(|l| Box::new(Value::Str(l.to_string()))).collect())),
+                Err(e) => Ok(Value::Error(e.0))
+            }
+        },
         Type::FileOut => Ok(Value::FileOut(s.to_string())),
         Type::Arr(ref bt) => {
             // multiple values either space or comma separated,
-            // but we will trim anyway
-            let parts: Vec<_> = if s.find(',').is_some() {
-                s.split(',').map(|s| s.trim()).collect()
+            // but we will trim anyway. Space-separated values may be quoted.
+            let parts: Vec<String> = if s.find(',').is_some() {
+                s.split(',').map(|s| s.trim().to_string()).collect()
             } else {
-                s.split_whitespace().collect()
+                tokenize(s)?
             };
             let mut res = Vec::new();
             for part in parts {
-                let v = bt.parse_string(part)?;
+                let v = bt.parse_string(&part)?;
                 res.push(Box::new(v));
             }
             Ok(Value::Arr(res))
           },
         Type::Path => Ok(Value::Path(s.into())),
-        _ => error(format!("can't convert '{}' to {:?}",s,self))
+        // kept as text, split when the map is built
+        Type::KeyVal =>
+            if s.find('=').is_some_and(|idx| idx > 0) {
+                Ok(Value::Str(s.to_string()))
+            } else {
+                Ok(Value::Error(format!("'{}' is not a key=value pair",s)))
+            },
+        // urls are kept as their normalized text
+        #[cfg(feature = "url-type")]
+        Type::Url =>
+            match ::url::Url::parse(s) {
+                Ok(u) => Ok(Value::Str(u.as_str().to_string())),
+                Err(e) => Ok(Value::Error(format!("'{}' is not a valid URL: {}",s,e)))
+            },
+        #[cfg(feature = "regex")]
+        Type::Regex =>
+            match ::regex::Regex::new(s) {
+                Ok(r) => Ok(Value::Regex(r)),
+                Err(e) => Ok(Value::Error(format!("'{}' is not a valid regex: {}",s,e)))
+            },
+        _ => lapp_error(format!("can't convert '{}' to {:?}",s,self))
         }
     }
 
@@ -148,11 +360,18 @@ impl Type {
 pub enum Value {
     Str(String),
     Int(i32),
+    U32(u32),
+    U64(u64),
+    USize(usize),
+    I64(i64),
     Float(f32),
+    F64(f64),
     Bool(bool),
     FileIn(String),
     FileOut(String),
     Path(PathBuf),
+    #[cfg(feature = "regex")]
+    Regex(::regex::Regex),
     None,
     Arr(Vec<Box<Value>>),
     Error(String),
@@ -164,21 +383,59 @@ impl Default for Value {
 
 impl Value {
     fn type_error<T>(&self, kind: &str) -> Result<T> {
-        error(format!("not a {}, but {}",kind,self.type_of().short_name()))
+        lapp_error(format!("not a {}, but {}",kind,self.type_of().short_name()))
     }
 
     pub fn as_string(&self) -> Result<String> {
         match *self { Value::Str(ref s) => Ok(s.clone()), _ => self.type_error("string") }
     }
 
+    pub fn as_str(&self) -> Result<&str> {
+        match *self { Value::Str(ref s) => Ok(s), _ => self.type_error("string") }
+    }
+
     pub fn as_int(&self) -> Result<i32> {
         match *self { Value::Int(n) => Ok(n), _ => self.type_error("integer" )}
     }
 
+    pub fn as_u32(&self) -> Result<u32> {
+        match *self { Value::U32(n) => Ok(n), _ => self.type_error("u32") }
+    }
+
+    pub fn as_u64(&self) -> Result<u64> {
+        match *self { Value::U64(n) => Ok(n), _ => self.type_error("u64") }
+    }
+
+    pub fn as_usize(&self) -> Result<usize> {
+        match *self { Value::USize(n) => Ok(n), _ => self.type_error("usize") }
+    }
+
+    pub fn as_i64(&self) -> Result<i64> {
+        match *self { Value::I64(n) => Ok(n), _ => self.type_error("i64") }
+    }
+
+    pub fn as_f64(&self) -> Result<f64> {
+        match *self { Value::F64(x) => Ok(x), _ => self.type_error("f64") }
+    }
+
     pub fn as_float(&self) -> Result<f32> {
         match *self { Value::Float(x) => Ok(x), _ => self.type_error("float") }
     }
 
+    // integers are widened
+    pub fn as_number(&self) -> Result<f64> {
+        match *self {
+            Value::Int(n) => Ok(n as f64),
+            Value::U32(n) => Ok(n as f64),
+            Value::U64(n) => Ok(n as f64),
+            Value::USize(n) => Ok(n as f64),
+            Value::I64(n) => Ok(n as f64),
+            Value::Float(x) => Ok(x as f64),
+            Value::F64(x) => Ok(x),
+            _ => self.type_error("number")
+        }
+    }
+
     pub fn as_bool(&self) -> Result<bool> {
         match *self { Value::Bool(b) => Ok(b), _ => self.type_error("boolean") }
     }
@@ -186,10 +443,10 @@ impl Value {
     pub fn as_infile(&self) -> Result<Box<Read>> {
         match *self {
              Value::FileIn(ref s) => {
-                if s == "stdin" { return Ok(Box::new(io::stdin())); }
+                if s == "stdin" || s == "-" { return Ok(Box::new(io::stdin())); }
                 match File::open(s) {
                     Ok(f) => Ok(Box::new(f)),
-                    Err(e) => error(format!("can't open '{}' for reading: {}",s, e.description()))
+                    Err(e) => lapp_error(format!("can't open '{}' for reading: {}",s,e))
                 }
              },
              _ => self.type_error("infile")
@@ -199,10 +456,10 @@ impl Value {
     pub fn as_outfile(&self) -> Result<Box<Write>> {
         match *self {
              Value::FileOut(ref s) => {
-                if s == "stdout" { return Ok(Box::new(io::stdout())); }
+                if s == "stdout" || s == "-" { return Ok(Box::new(io::stdout())); }
                 match File::create(s) {
                     Ok(f) => Ok(Box::new(f)),
-                    Err(e) => error(format!("can't open '{}' for writing: {}",s, e.description()))
+                    Err(e) => lapp_error(format!("can't open '{}' for writing: {}",s,e))
                 }
              },
              _ => self.type_error("outfile")
@@ -220,6 +477,11 @@ impl Value {
     }
 
 
+    #[cfg(feature = "regex")]
+    pub fn as_regex(&self) -> Result<::regex::Regex> {
+        match *self { Value::Regex(ref r) => Ok(r.clone()), _ => self.type_error("regex") }
+    }
+
     pub fn as_array(&self) -> Result<&Vec<Box<Value>>> {
         match *self {
             Value::Arr(ref vi) => Ok(vi),
@@ -231,20 +493,81 @@ impl Value {
         match *self {
         Value::Str(_) => Type::Str,
         Value::Int(_) => Type::Int,
+        Value::U32(_) => Type::U32,
+        Value::U64(_) => Type::U64,
+        Value::USize(_) => Type::USize,
+        Value::I64(_) => Type::I64,
         Value::Float(_) => Type::Float,
+        Value::F64(_) => Type::F64,
         Value::Bool(_) => Type::Bool,
         Value::FileIn(_) => Type::FileIn,
         Value::FileOut(_) => Type::FileOut,
         Value::Path(_) => Type::Path,
+        #[cfg(feature = "regex")]
+        Value::Regex(_) => Type::Regex,
         Value::None => Type::None,
         Value::Error(_) => Type::Error,
-        // watch out here...
-        Value::Arr(ref v) => Type::Arr(Box::new((*v[0]).type_of()))
+        // element type of an empty array is not known,
+        // and the elements must all have the same type
+        Value::Arr(ref v) => {
+            let t = v.first().map(|e| e.type_of()).unwrap_or(Type::None);
+            if v.iter().skip(1).any(|e| e.type_of() != t) {
+                Type::Error
+            } else {
+                Type::Arr(Box::new(t))
+            }
+        }
         }
     }
 
     // This converts the '(default STR)' specifier into the actual value (and hence type)
     pub fn from_value (val: &str, dtype: &Type) -> Result<Value> {
+        // variables are expanded once; their values are taken literally
+        let expanded;
+        let val = if val.contains("${") {
+            expanded = expand_env_vars(val)?;
+            expanded.as_str()
+        } else {
+            val
+        };
+        if val.is_empty() {
+            return lapp_error("default is empty");
+        }
+        // hex defaults need not start with a digit
+        if let Type::Hex | Type::Radix(_) | Type::BoolString = *dtype {
+            return dtype.parse_string(val);
+        }
+        #[cfg(feature = "url-type")]
+        {
+            if let Type::Url = *dtype {
+                return dtype.parse_string(val.trim_matches('\''));
+            }
+        }
+        #[cfg(feature = "regex")]
+        {
+            if let Type::Regex = *dtype {
+                return dtype.parse_string(val.trim_matches('\''));
+            }
+        }
+        // an email default must look like one, quoted or not
+        if let Type::Email = *dtype {
+            let unquoted = val.trim_matches(|c| c == '\'' || c == '"');
+            return match dtype.parse_string(unquoted)? {
+                Value::Error(e) => lapp_error(e),
+                v => Ok(v)
+            };
+        }
+        // the file is only read if the default is actually used
+        if let Type::FileInLines = *dtype {
+            return Ok(Value::FileIn(val.trim_matches('\'').into()));
+        }
+        if let Type::Bool = *dtype {
+            return match val {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => lapp_error(format!("bool default must be true or false, not '{}'",val))
+            };
+        }
         let firstc = val.chars().next().unwrap();
         if firstc.is_digit(10) {
             let dt;
@@ -256,15 +579,15 @@ impl Value {
             };
             t.parse_string(val)
         } else
-        if firstc == '\'' { // strip quotes, _definitely_ a string
+        if firstc == '\'' || firstc == '"' { // strip quotes, _definitely_ a string
             match *dtype {
-                Type::None | Type::Str => {},
+                Type::None | Type::Str | Type::Password => {},
                 _ =>
-                    return error(format!("cannot convert default string to {}",dtype.short_name()))
+                    return lapp_error(format!("cannot convert default string to {}",dtype.short_name()))
             }
             Ok(Value::Str((&val[1..(val.len()-1)]).into()))
         } else
-        if let Type::Str = *dtype {
+        if let Type::Str | Type::Password = *dtype {
             Ok(Value::Str(val.into()))
         } else
         if val == "stdin" {
@@ -290,6 +613,30 @@ impl Value {
         Value::Arr(empty)
     }
 
+    // combine with a value from a lower-priority source: arrays are joined,
+    // bools are or-ed and otherwise this value wins
+    pub fn merge(&self, other: &Value) -> Result<Value> {
+        match (self, other) {
+            (Value::Arr(a), Value::Arr(b)) => {
+                if let (Some(x), Some(y)) = (a.first(), b.first()) {
+                    if x.type_of() != y.type_of() {
+                        return lapp_error(format!("cannot merge {} with {}",self.type_of().short_name(),other.type_of().short_name()));
+                    }
+                }
+                Ok(Value::Arr(a.iter().chain(b.iter()).cloned().collect()))
+            },
+            (Value::Arr(_), _) | (_, Value::Arr(_)) =>
+                lapp_error("cannot merge array with non-array value"),
+            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(*a || *b)),
+            _ => {
+                if self.type_of() != other.type_of() {
+                    return lapp_error(format!("cannot merge {} with {}",self.type_of().short_name(),other.type_of().short_name()));
+                }
+                Ok(self.clone())
+            }
+        }
+    }
+
     pub fn is_none(&self) -> bool {
         match *self {
             Value::None => true,